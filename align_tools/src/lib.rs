@@ -188,6 +188,24 @@ pub fn summary_more(x: &DnaString, y: &DnaString, a: &Alignment) -> String {
     format!("{}", x.iter().format("\n"))
 }
 
+// Return the fraction of the query (x) and of the reference (y) that are covered by an
+// alignment, as the pair (query_coverage, reference_coverage).  Clipped bases are not covered.
+// An empty sequence is assigned coverage zero.
+
+pub fn coverage(a: &Alignment) -> (f64, f64) {
+    let frac = |start: usize, stop: usize, len: usize| {
+        if len == 0 {
+            0.0
+        } else {
+            (stop - start) as f64 / len as f64
+        }
+    };
+    (
+        frac(a.xstart, a.xend, a.xlen),
+        frac(a.ystart, a.yend, a.ylen),
+    )
+}
+
 // Return a "standard" affine alignment of x to y.  This is intended to be
 // applied to the case where x is to be fully aligned to part of y.
 
@@ -197,11 +215,27 @@ pub fn affine_align(x: &DnaString, y: &DnaString) -> Alignment {
     aligner.semiglobal(&x.to_ascii_vec(), &y.to_ascii_vec())
 }
 
-// Exhibit a "visual" version of an alignment.  This assumes that only certain alignment operations
-// are used and would need to be tweaked if other operations are present.  You can set width to
-// the expected terminal width.
+// Return the score of affine_align(x, y).  The underlying aligner (bio_edit) always computes
+// a traceback, so this is no faster than affine_align, and is provided for clarity at call sites
+// where only the score is needed.  If a score-only path is added to bio_edit, this can use it,
+// which would save the traceback memory and the construction of the operations vector, but
+// not the O(|x| * |y|) dynamic programming.
 
-pub fn vis_align(s1: &[u8], s2: &[u8], ops: &[AlignmentOperation], width: usize) -> String {
+pub fn affine_align_score(x: &DnaString, y: &DnaString) -> i32 {
+    affine_align(x, y).score
+}
+
+// Compute the rows of a "visual" version of an alignment: (s1 with gaps, match track, s2 with
+// gaps).  The rows have equal length.  Gaps are shown as spaces, and in the match track, a match
+// is shown as a space, a substitution as *, and an insertion or deletion as |.  This assumes
+// that only Match, Subst, Del and Ins operations are used and would need to be tweaked if other
+// operations are present.
+
+pub fn aligned_rows(
+    s1: &[u8],
+    s2: &[u8],
+    ops: &[AlignmentOperation],
+) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
     let (mut pos1, mut pos2) = (0, 0);
     let (mut t1, mut t2) = (Vec::<u8>::new(), Vec::<u8>::new());
     let mut d = Vec::<u8>::new();
@@ -239,6 +273,14 @@ pub fn vis_align(s1: &[u8], s2: &[u8], ops: &[AlignmentOperation], width: usize)
             panic!("unknown operation {:?}", ops[i]);
         }
     }
+    (t1, d, t2)
+}
+
+// Exhibit a "visual" version of an alignment, as given by aligned_rows, showing the match track
+// above the two sequences.  You can set width to the expected terminal width.
+
+pub fn vis_align(s1: &[u8], s2: &[u8], ops: &[AlignmentOperation], width: usize) -> String {
+    let (t1, d, t2) = aligned_rows(s1, s2, ops);
     let n = t1.len(); // = t2.len()
     let mut x = Vec::<u8>::new();
     let mut start = 0;
@@ -255,3 +297,47 @@ pub fn vis_align(s1: &[u8], s2: &[u8], ops: &[AlignmentOperation], width: usize)
     }
     stringme(&x)
 }
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p align_tools
+
+    use crate::{affine_align, affine_align_score, aligned_rows, coverage, vis_align};
+    use bio_edit::alignment::AlignmentOperation::{Ins, Match, Subst};
+    use debruijn::dna_string::DnaString;
+    use string_utils::strme;
+
+    #[test]
+    fn test_affine_align_score() {
+        let x = DnaString::from_dna_string("ACCGTGGAT");
+        let y = DnaString::from_dna_string("AAAAACCGTTGATTTT");
+        assert_eq!(affine_align_score(&x, &y), affine_align(&x, &y).score);
+        assert_eq!(affine_align_score(&x, &y), 7);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let x = DnaString::from_dna_string("ACCGTGGAT");
+        let y = DnaString::from_dna_string("AAAAACCGTTGATTTTAAAA");
+        let a = affine_align(&x, &y);
+        let (qcov, rcov) = coverage(&a);
+        assert_eq!(qcov, 1.0);
+        assert_eq!(rcov, 9.0 / 20.0);
+    }
+
+    #[test]
+    fn test_aligned_rows() {
+        let (s1, s2) = (b"ACGTTA", b"AGGTA");
+        let ops = [Match, Subst, Match, Match, Ins, Match];
+        let (t1, d, t2) = aligned_rows(s1, s2, &ops);
+        assert_eq!(strme(&t1), "ACGTTA");
+        assert_eq!(strme(&d), " *  | ");
+        assert_eq!(strme(&t2), "AGGT A");
+        assert_eq!(
+            vis_align(s1, s2, &ops, 4),
+            " *  \nACGT\nAGGT\n\n| \nTA\n A\n"
+        );
+    }
+}
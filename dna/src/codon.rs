@@ -0,0 +1,216 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+// Back-translation of protein sequences to DNA, using a codon usage table.  This is the
+// (lossy) inverse of translation: for each amino acid, the most frequently used codon is chosen.
+// If several codons for an amino acid are equally frequent, the lexicographically smallest one
+// is chosen, so the result is deterministic.
+
+use std::collections::HashMap;
+
+// The standard genetic code, together with codon frequencies in Homo sapiens, given as uses per
+// thousand codons, from the Codon Usage Database (Nakamura et al., Nucleic Acids Research 28
+// (2000), 292), https://www.kazusa.or.jp/codon.  Stop codons are denoted by *.
+
+const STANDARD_TABLE: [(&[u8; 3], u8, f64); 64] = [
+    (b"TTT", b'F', 17.6),
+    (b"TTC", b'F', 20.3),
+    (b"TTA", b'L', 7.7),
+    (b"TTG", b'L', 12.9),
+    (b"TCT", b'S', 15.2),
+    (b"TCC", b'S', 17.7),
+    (b"TCA", b'S', 12.2),
+    (b"TCG", b'S', 4.4),
+    (b"TAT", b'Y', 12.2),
+    (b"TAC", b'Y', 15.3),
+    (b"TAA", b'*', 1.0),
+    (b"TAG", b'*', 0.8),
+    (b"TGT", b'C', 10.6),
+    (b"TGC", b'C', 12.6),
+    (b"TGA", b'*', 1.6),
+    (b"TGG", b'W', 13.2),
+    (b"CTT", b'L', 13.2),
+    (b"CTC", b'L', 19.6),
+    (b"CTA", b'L', 7.2),
+    (b"CTG", b'L', 39.6),
+    (b"CCT", b'P', 17.5),
+    (b"CCC", b'P', 19.8),
+    (b"CCA", b'P', 16.9),
+    (b"CCG", b'P', 6.9),
+    (b"CAT", b'H', 10.9),
+    (b"CAC", b'H', 15.1),
+    (b"CAA", b'Q', 12.3),
+    (b"CAG", b'Q', 34.2),
+    (b"CGT", b'R', 4.5),
+    (b"CGC", b'R', 10.4),
+    (b"CGA", b'R', 6.2),
+    (b"CGG", b'R', 11.4),
+    (b"ATT", b'I', 16.0),
+    (b"ATC", b'I', 20.8),
+    (b"ATA", b'I', 7.5),
+    (b"ATG", b'M', 22.0),
+    (b"ACT", b'T', 13.1),
+    (b"ACC", b'T', 18.9),
+    (b"ACA", b'T', 15.1),
+    (b"ACG", b'T', 6.1),
+    (b"AAT", b'N', 17.0),
+    (b"AAC", b'N', 19.1),
+    (b"AAA", b'K', 24.4),
+    (b"AAG", b'K', 31.9),
+    (b"AGT", b'S', 12.1),
+    (b"AGC", b'S', 19.5),
+    (b"AGA", b'R', 12.2),
+    (b"AGG", b'R', 12.0),
+    (b"GTT", b'V', 11.0),
+    (b"GTC", b'V', 14.5),
+    (b"GTA", b'V', 7.1),
+    (b"GTG", b'V', 28.1),
+    (b"GCT", b'A', 18.4),
+    (b"GCC", b'A', 27.7),
+    (b"GCA", b'A', 15.8),
+    (b"GCG", b'A', 7.4),
+    (b"GAT", b'D', 21.8),
+    (b"GAC", b'D', 25.1),
+    (b"GAA", b'E', 29.0),
+    (b"GAG", b'E', 39.6),
+    (b"GGT", b'G', 10.8),
+    (b"GGC", b'G', 22.2),
+    (b"GGA", b'G', 16.5),
+    (b"GGG", b'G', 16.5),
+];
+
+// The twenty amino acids, each of which must be represented in a codon usage table.
+
+const AMINO_ACIDS: &[u8; 20] = b"ACDEFGHIKLMNPQRSTVWY";
+
+// Translate a codon using the standard genetic code.
+
+fn standard_codon_to_aa(codon: &[u8]) -> Option<u8> {
+    STANDARD_TABLE
+        .iter()
+        .find(|x| &x.0[..] == codon)
+        .map(|x| x.1)
+}
+
+// CodonUsage: for each amino acid (and possibly the stop symbol *), the codon to be used for it.
+
+pub struct CodonUsage {
+    best: HashMap<u8, [u8; 3]>,
+}
+
+impl CodonUsage {
+    // Create a codon usage table for Homo sapiens.
+
+    pub fn human() -> CodonUsage {
+        let freqs = STANDARD_TABLE
+            .iter()
+            .map(|x| (&x.0[..], x.2))
+            .collect::<Vec<_>>();
+        CodonUsage::from_frequencies(&freqs).unwrap()
+    }
+
+    // Create a codon usage table from user-supplied (codon, frequency) pairs.  The frequencies
+    // need only be comparable to each other, so they may be counts, fractions or uses per
+    // thousand.  Codons are translated using the standard genetic code.  Codons that are not
+    // listed are never used.  It is an error if a codon is invalid or listed more than once,
+    // if a frequency is negative or not finite, or if some amino acid has no codon.  Stop codons
+    // are optional, but if none are given, a protein containing * cannot be back-translated.
+
+    pub fn from_frequencies(freqs: &[(&[u8], f64)]) -> Result<CodonUsage, String> {
+        let mut freqs = freqs.to_vec();
+        freqs.sort_by(|a, b| a.0.cmp(b.0));
+        let mut best = HashMap::<u8, ([u8; 3], f64)>::new();
+        for (i, &(codon, f)) in freqs.iter().enumerate() {
+            let name = String::from_utf8_lossy(codon);
+            let aa = match standard_codon_to_aa(codon) {
+                Some(aa) => aa,
+                None => return Err(format!("invalid codon {}", name)),
+            };
+            if i > 0 && freqs[i - 1].0 == codon {
+                return Err(format!("codon {} is listed more than once", name));
+            }
+            if !f.is_finite() || f < 0.0 {
+                return Err(format!("codon {} has invalid frequency {}", name, f));
+            }
+            let c = [codon[0], codon[1], codon[2]];
+            let e = best.entry(aa).or_insert((c, f));
+            if f > e.1 {
+                *e = (c, f);
+            }
+        }
+        for aa in AMINO_ACIDS.iter() {
+            if !best.contains_key(aa) {
+                return Err(format!("amino acid {} has no codon", *aa as char));
+            }
+        }
+        Ok(CodonUsage {
+            best: best.into_iter().map(|(aa, (c, _))| (aa, c)).collect(),
+        })
+    }
+
+    // Return the codon used for an amino acid, or None if there is none.
+
+    pub fn codon(&self, aa: u8) -> Option<&[u8; 3]> {
+        self.best.get(&aa)
+    }
+}
+
+// Back-translate a protein sequence, given using one-letter amino acid codes, choosing for each
+// amino acid the most frequent codon in the given table.  Panics if the protein contains a
+// residue that the table has no codon for.
+
+pub fn back_translate(protein: &[u8], usage: &CodonUsage) -> String {
+    let mut s = String::with_capacity(3 * protein.len());
+    for &aa in protein {
+        match usage.codon(aa) {
+            Some(c) => s.push_str(std::str::from_utf8(c).unwrap()),
+            None => panic!("back_translate: no codon for residue {}", aa as char),
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p dna test_back_translate
+
+    use crate::codon::{
+        back_translate, standard_codon_to_aa, CodonUsage, AMINO_ACIDS, STANDARD_TABLE,
+    };
+
+    #[test]
+    fn test_back_translate() {
+        let human = CodonUsage::human();
+        assert_eq!(back_translate(b"MAG*", &human), "ATGGCCGGCTGA");
+        assert_eq!(back_translate(b"", &human), "");
+
+        // Check the tie-break, and translation back to the protein.
+
+        let mut freqs = STANDARD_TABLE
+            .iter()
+            .map(|x| (&x.0[..], x.2))
+            .filter(|x| x.0 != b"GGC")
+            .collect::<Vec<_>>();
+        let usage = CodonUsage::from_frequencies(&freqs).unwrap();
+        assert_eq!(back_translate(b"G", &usage), "GGA");
+        let protein = AMINO_ACIDS;
+        let dna = back_translate(protein, &usage);
+        for (i, &aa) in protein.iter().enumerate() {
+            let codon = &dna.as_bytes()[3 * i..3 * i + 3];
+            assert_eq!(standard_codon_to_aa(codon), Some(aa));
+        }
+
+        // Check validation.
+
+        freqs.retain(|x| x.0 != b"TGG");
+        assert!(CodonUsage::from_frequencies(&freqs).is_err());
+        assert!(CodonUsage::from_frequencies(&[(b"ATGC", 1.0)]).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_back_translate_unknown() {
+        back_translate(b"MXA", &CodonUsage::human());
+    }
+}
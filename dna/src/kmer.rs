@@ -0,0 +1,84 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+// Alignment-free comparison of DNA sequences using kmer spectra.
+
+use crate::{seq::rc, verify_dna};
+use std::collections::HashMap;
+
+// Count the canonical kmers in a sequence, where the canonical form of a kmer is the min of it
+// and its reverse complement.
+
+fn canonical_kmer_counts(s: &str, k: usize) -> HashMap<Vec<u8>, usize> {
+    let mut counts = HashMap::<Vec<u8>, usize>::new();
+    let s = s.as_bytes();
+    if s.len() >= k {
+        for kmer in s.windows(k) {
+            let kmer_rc = rc(kmer);
+            let canon = if *kmer <= *kmer_rc {
+                kmer.to_vec()
+            } else {
+                kmer_rc
+            };
+            *counts.entry(canon).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Compute the cosine distance between the canonical kmer count vectors of two DNA sequences,
+// i.e. one minus the cosine of the angle between them.  This is a quick way to screen for
+// similar sequences without aligning them.  Since counts are nonnegative, the distance lies
+// between 0, for sequences having proportional spectra (e.g. identical sequences, or a sequence
+// and its reverse complement), and 1, for sequences having no canonical kmer in common.  If
+// neither sequence has a kmer (length ≥ k), the distance is 0, and if only one of them does, it
+// is 1.  The sequences must consist of A, C, G and T only.
+
+pub fn kmer_spectrum_distance(a: &str, b: &str, k: usize) -> f64 {
+    assert!(k > 0);
+    verify_dna(a);
+    verify_dna(b);
+    let (ca, cb) = (canonical_kmer_counts(a, k), canonical_kmer_counts(b, k));
+    if ca.is_empty() || cb.is_empty() {
+        return if ca.is_empty() && cb.is_empty() {
+            0.0
+        } else {
+            1.0
+        };
+    }
+    let norm = |c: &HashMap<Vec<u8>, usize>| {
+        c.values()
+            .map(|&n| (n as f64) * (n as f64))
+            .sum::<f64>()
+            .sqrt()
+    };
+    let mut dot = 0.0;
+    for (kmer, &n) in ca.iter() {
+        if let Some(&m) = cb.get(kmer) {
+            dot += n as f64 * m as f64;
+        }
+    }
+    let d = 1.0 - dot / (norm(&ca) * norm(&cb));
+    d.max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p dna test_kmer_spectrum_distance
+
+    use crate::kmer::{kmer_spectrum_distance, rc};
+
+    #[test]
+    fn test_kmer_spectrum_distance() {
+        let s = "ACGTTGCATGCCAGTAGGCTA";
+        assert!(kmer_spectrum_distance(s, s, 4).abs() < 1e-12);
+        let r = String::from_utf8(rc(s.as_bytes())).unwrap();
+        assert!(kmer_spectrum_distance(s, &r, 4).abs() < 1e-12);
+        assert_eq!(kmer_spectrum_distance("AAAAAAAAAA", "CGCGCGCGCG", 3), 1.0);
+        let d = kmer_spectrum_distance(s, "ACGTTGCATGCCAGTAGGCTT", 4);
+        assert!(d > 0.0 && d < 0.2);
+        assert_eq!(kmer_spectrum_distance("AC", "GT", 3), 0.0);
+        assert_eq!(kmer_spectrum_distance("ACG", "GT", 3), 1.0);
+    }
+}
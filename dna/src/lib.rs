@@ -1,5 +1,15 @@
 // Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
 
+pub mod codon;
+pub mod kmer;
+pub mod primer;
+pub mod quality;
+pub mod seq;
+
+use lazy_static::lazy_static;
+use seq::{gc_content, iupac_bases};
+use std::fmt;
+
 // This file provides a function tm_nearest_neighbor.  All the code in this file is a verbatim
 // translation to rust of C++ code in the BroadCRD codebase (copyright 2006), as
 // dna/DNAHybridization.{cc,h}.  It is conceivable that mistakes were introduced in translation.
@@ -38,39 +48,125 @@
 // Alternatively, if S contains + symbols, they will be interpreted as instructions
 // to "lock the following nucleotide", and the vector "locked" will be generated
 // accordingly.
+//
+// The function tm_nearest_neighbor_full_salt also allows for Mg2+ ions, at concentration
+// mg_mol, some of which are bound by dNTPs, at concentration dntp_mol, following
+//
+// [4] Owczarzy, et al.  Predicting stability of DNA duplexes in solutions containing
+// magnesium and monovalent cations.  Biochemistry 47 (2008), 5336-5353.
+//
+// If there is no free Mg2+, or if monovalent ions dominate, the Na+ correction of [2] is used.
 
 pub fn tm_nearest_neighbor(s: &str) -> f64 {
+    try_tm_nearest_neighbor(s).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// Same as tm_nearest_neighbor, but return an error if s is not a valid input.
+
+pub fn try_tm_nearest_neighbor(s: &str) -> Result<f64, DnaError> {
     let locked = Vec::<bool>::new();
-    tm_nearest_neighbor_full(s, 0.00000025, 0.05, &locked)
+    try_tm_nearest_neighbor_full(s, 0.00000025, 0.05, &locked)
 }
 
 pub fn tm_nearest_neighbor_full(s: &str, s_mol: f64, na_mol: f64, locked: &[bool]) -> f64 {
+    try_tm_nearest_neighbor_full(s, s_mol, na_mol, locked).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn tm_nearest_neighbor_full_salt(
+    s: &str,
+    s_mol: f64,
+    na_mol: f64,
+    mg_mol: f64,
+    dntp_mol: f64,
+    locked: &[bool],
+) -> f64 {
+    try_tm_nearest_neighbor_full_salt(s, s_mol, na_mol, mg_mol, dntp_mol, locked)
+        .unwrap_or_else(|e| panic!("{}", e))
+}
+
+// Same as tm_nearest_neighbor_full, but return an error if s is not a valid input, i.e. if it
+// is empty, or contains a character other than A, C, G, T or +, or has a + that is not
+// followed by a base, or if locked is nonempty and its length differs from that of s, or if
+// the locked bases (however specified) do not satisfy the assumptions (a) and (b) above.  For
+// locked bases, positions ignore + symbols.
+
+pub fn try_tm_nearest_neighbor_full(
+    s: &str,
+    s_mol: f64,
+    na_mol: f64,
+    locked: &[bool],
+) -> Result<f64, DnaError> {
+    try_tm_nearest_neighbor_full_salt(s, s_mol, na_mol, 0.0, 0.0, locked)
+}
+
+pub fn try_tm_nearest_neighbor_full_salt(
+    s: &str,
+    s_mol: f64,
+    na_mol: f64,
+    mg_mol: f64,
+    dntp_mol: f64,
+    locked: &[bool],
+) -> Result<f64, DnaError> {
     // Allow for + symbols.
 
     if s.contains('+') {
-        assert_eq!(locked.len(), 0);
-        assert!(!s.contains("++"));
-        assert!(!s.ends_with('+'));
+        if !locked.is_empty() {
+            return Err(DnaError::PlusWithLocked);
+        }
         let mut sx = String::new();
         let schars: Vec<char> = s.chars().collect();
         let mut lockedx = Vec::<bool>::with_capacity(schars.len());
+        for (pos, &base) in schars.iter().enumerate() {
+            if !matches!(base, 'A' | 'C' | 'G' | 'T' | '+') {
+                return Err(DnaError::InvalidBase { base, pos });
+            }
+        }
         let mut i = 0;
         while i < schars.len() {
             if schars[i] != '+' {
                 sx.push(schars[i]);
                 lockedx.push(false);
             } else {
+                if i + 1 == schars.len() {
+                    return Err(DnaError::TrailingPlus);
+                }
+                if schars[i + 1] == '+' {
+                    return Err(DnaError::DoublePlus { pos: i });
+                }
                 sx.push(schars[i + 1]);
                 lockedx.push(true);
                 i += 1;
             }
+            i += 1;
         }
-        return tm_nearest_neighbor_full(&sx, s_mol, na_mol, &lockedx);
+        return try_tm_nearest_neighbor_full_salt(&sx, s_mol, na_mol, mg_mol, dntp_mol, &lockedx);
     }
 
-    // Validate sequence.
+    // Validate sequence and locked bases.
 
-    verify_dna(s);
+    try_verify_dna(s)?;
+    if s.is_empty() {
+        return Err(DnaError::EmptySequence);
+    }
+    if !locked.is_empty() && locked.len() != s.len() {
+        return Err(DnaError::LockedLengthMismatch {
+            len: locked.len(),
+            expected: s.len(),
+        });
+    }
+    if locked.iter().any(|&l| l) {
+        let n = locked.len();
+        for i in 0..n {
+            if locked[i] {
+                if i < 2 || i + 2 >= n {
+                    return Err(DnaError::LockedNearEnd { pos: i });
+                }
+                if locked[i - 1] {
+                    return Err(DnaError::AdjacentLocked { pos: i });
+                }
+            }
+        }
+    }
 
     // Compute thermodynamic sums.
 
@@ -85,33 +181,209 @@ pub fn tm_nearest_neighbor_full(s: &str, s_mol: f64, na_mol: f64, locked: &[bool
     let kelvin_to_celsius = 273.15;
     let mut temp = 1000.0 * dh_sum / (ds_sum + ideal_gas_const * s_mol.ln()) - kelvin_to_celsius;
 
-    // Correct for Na concentration, following [2].
+    // Find the concentration of free Mg2+, using the association constant Ka = 3 x 10^4
+    // for Mg2+ and dNTPs, following [4].
 
-    let mut sx = Vec::<char>::new();
-    for c in s.chars() {
-        sx.push(c);
+    let gc_fract = gc_content(s);
+    let mut mg_free = 0.0;
+    if mg_mol > 0.0 {
+        let ka = 30000.0;
+        let b = ka * dntp_mol - ka * mg_mol + 1.0;
+        mg_free = (-b + (b * b + 4.0 * ka * mg_mol).sqrt()) / (2.0 * ka);
     }
-    let mut gc = 0;
-    for i in 0..sx.len() {
-        if sx[i] == 'G' || sx[i] == 'C' {
-            gc += 1;
+    let ratio = if na_mol > 0.0 {
+        mg_free.sqrt() / na_mol
+    } else {
+        f64::INFINITY
+    };
+    if mg_free <= 0.0 || ratio < 0.22 {
+        // Correct for Na concentration, following [2].
+
+        let ln_na_mol = na_mol.ln();
+        temp = -kelvin_to_celsius
+            + 1.0
+                / (1.0 / (temp + kelvin_to_celsius)
+                    + (4.29 * gc_fract - 3.95) * 0.00001 * ln_na_mol
+                    + 9.40 * 0.000001 * ln_na_mol * ln_na_mol);
+    } else {
+        // Correct for Mg concentration, following [4], equation 16.  If the ratio is less than
+        // 6, some of the coefficients depend on the Na concentration.
+
+        let (mut a, b, c, mut d, e, f, mut g) = (
+            3.92e-5, -9.11e-6, 6.26e-5, 1.42e-5, -4.82e-4, 5.25e-4, 8.31e-5,
+        );
+        if ratio < 6.0 {
+            let ln_na_mol = na_mol.ln();
+            a *= 0.843 - 0.352 * na_mol.sqrt() * ln_na_mol;
+            d *= 1.279 - 4.03e-3 * ln_na_mol - 8.03e-3 * ln_na_mol * ln_na_mol;
+            g *= 0.486 - 0.258 * ln_na_mol + 5.25e-3 * ln_na_mol * ln_na_mol * ln_na_mol;
         }
+        let ln_mg = mg_free.ln();
+        let n = s.len() as f64;
+        temp = -kelvin_to_celsius
+            + 1.0
+                / (1.0 / (temp + kelvin_to_celsius)
+                    + a
+                    + b * ln_mg
+                    + gc_fract * (c + d * ln_mg)
+                    + (e + f * ln_mg + g * ln_mg * ln_mg) / (2.0 * (n - 1.0)));
     }
-    let gc_fract = gc as f64 / sx.len() as f64;
-    let ln_na_mol = na_mol.ln();
-
-    temp = -kelvin_to_celsius
-        + 1.0
-            / (1.0 / (temp + kelvin_to_celsius)
-                + (4.29 * gc_fract - 3.95) * 0.00001 * ln_na_mol
-                + 9.40 * 0.000001 * ln_na_mol * ln_na_mol);
-    temp
+    Ok(temp)
 }
 
+// tm_nearest_neighbor_batch: same as calling tm_nearest_neighbor_full on each of the given
+// sequences, with no locked bases (except as specified by + symbols).
+
+pub fn tm_nearest_neighbor_batch(seqs: &[&str], s_mol: f64, na_mol: f64) -> Vec<f64> {
+    seqs.iter()
+        .map(|s| tm_nearest_neighbor_full(s, s_mol, na_mol, &[]))
+        .collect()
+}
+
+// tm_range_iupac: find the minimum and maximum of tm_nearest_neighbor over all the sequences
+// obtained by resolving the IUPAC ambiguity codes in s, e.g. N may be any base.  Since the
+// number of such sequences grows exponentially with the number of ambiguous bases, this
+// panics if there are more than MAX_IUPAC_RESOLUTIONS of them.  Use try_tm_range_iupac to
+// instead get an error, or to set the limit.  Locked bases may be specified using +.
+
+pub const MAX_IUPAC_RESOLUTIONS: usize = 4096;
+
+pub fn tm_range_iupac(s: &str) -> (f64, f64) {
+    try_tm_range_iupac(s, MAX_IUPAC_RESOLUTIONS).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_tm_range_iupac(s: &str, max_resolutions: usize) -> Result<(f64, f64), DnaError> {
+    // Find the possible bases at each position, and the number of resolutions.
+
+    let mut choices = Vec::<&[u8]>::new();
+    let mut count = 1_usize;
+    for (pos, base) in s.chars().enumerate() {
+        let x = match base {
+            '+' => &b"+"[..],
+            _ => iupac_bases(base).ok_or(DnaError::InvalidBase { base, pos })?,
+        };
+        count = count.saturating_mul(x.len());
+        if count > max_resolutions {
+            return Err(DnaError::TooManyResolutions {
+                max: max_resolutions,
+            });
+        }
+        choices.push(x);
+    }
+
+    // Go through the resolutions.  The choice of base at each position is given by index,
+    // which is advanced like an odometer.
+
+    let mut index = vec![0; choices.len()];
+    let mut x = Vec::<u8>::with_capacity(choices.len());
+    let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+    loop {
+        x.clear();
+        for i in 0..choices.len() {
+            x.push(choices[i][index[i]]);
+        }
+        let tm = try_tm_nearest_neighbor(std::str::from_utf8(&x).unwrap())?;
+        min = min.min(tm);
+        max = max.max(tm);
+        let mut i = 0;
+        while i < choices.len() && index[i] + 1 == choices[i].len() {
+            index[i] = 0;
+            i += 1;
+        }
+        if i == choices.len() {
+            break;
+        }
+        index[i] += 1;
+    }
+    Ok((min, max))
+}
+
+// DnaError: the ways in which a DNA sequence, or an input to tm_nearest_neighbor_full, can be
+// invalid.  Positions are zero-based, and count characters.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DnaError {
+    InvalidBase { base: char, pos: usize },
+    EmptySequence,
+    DoublePlus { pos: usize },
+    TrailingPlus,
+    PlusWithLocked,
+    LockedLengthMismatch { len: usize, expected: usize },
+    AdjacentLocked { pos: usize },
+    LockedNearEnd { pos: usize },
+    TooManyResolutions { max: usize },
+}
+
+impl fmt::Display for DnaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DnaError::InvalidBase { base, pos } => write!(
+                f,
+                "invalid base '{}' at position {}: only A, C, G and T are allowed",
+                base, pos
+            ),
+            DnaError::EmptySequence => write!(f, "the sequence is empty"),
+            DnaError::TooManyResolutions { max } => write!(
+                f,
+                "the ambiguity codes in the sequence have more than {} resolutions",
+                max
+            ),
+            DnaError::DoublePlus { pos } => write!(f, "++ at position {}", pos),
+            DnaError::TrailingPlus => write!(f, "the sequence ends with +"),
+            DnaError::PlusWithLocked => {
+                write!(
+                    f,
+                    "+ symbols may not be used if locked bases are also given"
+                )
+            }
+            DnaError::LockedLengthMismatch { len, expected } => write!(
+                f,
+                "locked has length {} but the sequence has length {}",
+                len, expected
+            ),
+            DnaError::AdjacentLocked { pos } => write!(
+                f,
+                "locked bases at positions {} and {}: two locked bases in a row are not allowed",
+                pos - 1,
+                pos
+            ),
+            DnaError::LockedNearEnd { pos } => write!(
+                f,
+                "locked base at position {}: locked bases are not allowed at or next to the \
+                 beginning or end",
+                pos
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DnaError {}
+
+// Check that a DNA sequence consists of A, C, G and T only.  verify_dna panics if not, and
+// try_verify_dna returns an error naming the first offending character.
+
 pub fn verify_dna(s: &str) {
-    for c in s.chars() {
-        assert!(c == 'A' || c == 'C' || c == 'G' || c == 'T');
+    try_verify_dna(s).unwrap_or_else(|e| panic!("{}", e));
+}
+
+pub fn try_verify_dna(s: &str) -> Result<(), DnaError> {
+    for (pos, base) in s.chars().enumerate() {
+        if !matches!(base, 'A' | 'C' | 'G' | 'T') {
+            return Err(DnaError::InvalidBase { base, pos });
+        }
+    }
+    Ok(())
+}
+
+// Same as try_verify_dna, but also allow IUPAC ambiguity codes, such as N.
+
+pub fn try_verify_dna_iupac(s: &str) -> Result<(), DnaError> {
+    for (pos, base) in s.chars().enumerate() {
+        if iupac_bases(base).is_none() {
+            return Err(DnaError::InvalidBase { base, pos });
+        }
     }
+    Ok(())
 }
 
 // get_thermodynamic_parameters_dna.
@@ -348,6 +620,107 @@ pub fn get_locked_thermodynamic_parameters_dna(
     ddg_right[t][t] = -0.212;
 }
 
+// ThermodynamicParameters: the parameters returned by get_thermodynamic_parameters_dna, as
+// fixed-size arrays.  LockedThermodynamicParameters: likewise for
+// get_locked_thermodynamic_parameters_dna.  Each is computed once, on first use, by
+// thermodynamic_parameters and locked_thermodynamic_parameters.
+
+#[derive(Default)]
+struct ThermodynamicParameters {
+    dh: [[f64; 4]; 4],
+    ds: [[f64; 4]; 4],
+    dg: [[f64; 4]; 4],
+    dh_g_or_c_init: f64,
+    dh_a_or_t_init: f64,
+    ds_g_or_c_init: f64,
+    ds_a_or_t_init: f64,
+    dg_g_or_c_init: f64,
+    dg_a_or_t_init: f64,
+    dh_symmetry_correction: f64,
+    ds_symmetry_correction: f64,
+    dg_symmetry_correction: f64,
+}
+
+#[derive(Default)]
+struct LockedThermodynamicParameters {
+    ddh_left: [[f64; 4]; 4],
+    dds_left: [[f64; 4]; 4],
+    ddg_left: [[f64; 4]; 4],
+    ddh_right: [[f64; 4]; 4],
+    dds_right: [[f64; 4]; 4],
+    ddg_right: [[f64; 4]; 4],
+}
+
+fn to_matrix(x: &[Vec<f64>]) -> [[f64; 4]; 4] {
+    let mut m = [[0.0; 4]; 4];
+    for (mi, xi) in m.iter_mut().zip(x.iter()) {
+        mi.copy_from_slice(xi);
+    }
+    m
+}
+
+impl ThermodynamicParameters {
+    fn new() -> ThermodynamicParameters {
+        let mut p = ThermodynamicParameters::default();
+        let (mut dh, mut ds, mut dg) = (Vec::new(), Vec::new(), Vec::new());
+        get_thermodynamic_parameters_dna(
+            &mut dh,
+            &mut ds,
+            &mut dg,
+            &mut p.dh_g_or_c_init,
+            &mut p.dh_a_or_t_init,
+            &mut p.ds_g_or_c_init,
+            &mut p.ds_a_or_t_init,
+            &mut p.dg_g_or_c_init,
+            &mut p.dg_a_or_t_init,
+            &mut p.dh_symmetry_correction,
+            &mut p.ds_symmetry_correction,
+            &mut p.dg_symmetry_correction,
+        );
+        p.dh = to_matrix(&dh);
+        p.ds = to_matrix(&ds);
+        p.dg = to_matrix(&dg);
+        p
+    }
+}
+
+impl LockedThermodynamicParameters {
+    fn new() -> LockedThermodynamicParameters {
+        let mut l = LockedThermodynamicParameters::default();
+        let (mut ddh_left, mut dds_left, mut ddg_left) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut ddh_right, mut dds_right, mut ddg_right) = (Vec::new(), Vec::new(), Vec::new());
+        get_locked_thermodynamic_parameters_dna(
+            &mut ddh_left,
+            &mut dds_left,
+            &mut ddg_left,
+            &mut ddh_right,
+            &mut dds_right,
+            &mut ddg_right,
+        );
+        l.ddh_left = to_matrix(&ddh_left);
+        l.dds_left = to_matrix(&dds_left);
+        l.ddg_left = to_matrix(&ddg_left);
+        l.ddh_right = to_matrix(&ddh_right);
+        l.dds_right = to_matrix(&dds_right);
+        l.ddg_right = to_matrix(&ddg_right);
+        l
+    }
+}
+
+lazy_static! {
+    static ref PARAMETERS: ThermodynamicParameters = ThermodynamicParameters::new();
+    static ref LOCKED_PARAMETERS: LockedThermodynamicParameters =
+        LockedThermodynamicParameters::new();
+}
+
+fn thermodynamic_parameters() -> &'static ThermodynamicParameters {
+    &PARAMETERS
+}
+
+fn locked_thermodynamic_parameters() -> &'static LockedThermodynamicParameters {
+    &LOCKED_PARAMETERS
+}
+
 // thermodynamic_sums_dna.  Compute dh_sum, ds_sum, dg_sum.  We allow some nucleotides
 // to be locked, as specified by the variable "locked".  The following assumptions
 // are enforced:
@@ -366,41 +739,14 @@ pub fn thermodynamic_sums_dna(
 ) {
     // defaults for last: true, true, empty
 
-    let mut dh = Vec::<Vec<f64>>::new();
-    let mut ds = Vec::<Vec<f64>>::new();
-    let mut dg = Vec::<Vec<f64>>::new();
-
-    let mut dh_g_or_c_init = 0.0;
-    let mut dh_a_or_t_init = 0.0;
-    let mut ds_g_or_c_init = 0.0;
-    let mut ds_a_or_t_init = 0.0;
-    let mut dg_g_or_c_init = 0.0;
-    let mut dg_a_or_t_init = 0.0;
-    let mut dh_symmetry_correction = 0.0;
-    let mut ds_symmetry_correction = 0.0;
-    let mut dg_symmetry_correction = 0.0;
-
-    get_thermodynamic_parameters_dna(
-        &mut dh,
-        &mut ds,
-        &mut dg,
-        &mut dh_g_or_c_init,
-        &mut dh_a_or_t_init,
-        &mut ds_g_or_c_init,
-        &mut ds_a_or_t_init,
-        &mut dg_g_or_c_init,
-        &mut dg_a_or_t_init,
-        &mut dh_symmetry_correction,
-        &mut ds_symmetry_correction,
-        &mut dg_symmetry_correction,
-    );
+    let p = thermodynamic_parameters();
     *dh_sum = 0.0;
     *ds_sum = 0.0;
     *dg_sum = 0.0;
     if include_symmetry_correction {
-        *dh_sum += dh_symmetry_correction;
-        *ds_sum += ds_symmetry_correction;
-        *dg_sum += dg_symmetry_correction;
+        *dh_sum += p.dh_symmetry_correction;
+        *ds_sum += p.ds_symmetry_correction;
+        *dg_sum += p.dg_symmetry_correction;
     }
     let mut sx = Vec::<char>::new();
     for c in s.chars() {
@@ -408,28 +754,24 @@ pub fn thermodynamic_sums_dna(
     }
     if include_initiation_terms {
         if sx[0] == 'A' || sx[0] == 'T' {
-            *dh_sum += dh_a_or_t_init;
-            *ds_sum += ds_a_or_t_init;
-            *dg_sum += dg_a_or_t_init;
+            *dh_sum += p.dh_a_or_t_init;
+            *ds_sum += p.ds_a_or_t_init;
+            *dg_sum += p.dg_a_or_t_init;
         } else {
-            *dh_sum += dh_g_or_c_init;
-            *ds_sum += ds_g_or_c_init;
-            *dg_sum += dg_g_or_c_init;
+            *dh_sum += p.dh_g_or_c_init;
+            *ds_sum += p.ds_g_or_c_init;
+            *dg_sum += p.dg_g_or_c_init;
         }
         if sx[sx.len() - 1] == 'A' || sx[sx.len() - 1] == 'T' {
-            *dh_sum += dh_a_or_t_init;
-            *ds_sum += ds_a_or_t_init;
-            *dg_sum += dg_a_or_t_init;
+            *dh_sum += p.dh_a_or_t_init;
+            *ds_sum += p.ds_a_or_t_init;
+            *dg_sum += p.dg_a_or_t_init;
         } else {
-            *dh_sum += dh_g_or_c_init;
-            *ds_sum += ds_g_or_c_init;
-            *dg_sum += dg_g_or_c_init;
+            *dh_sum += p.dh_g_or_c_init;
+            *ds_sum += p.ds_g_or_c_init;
+            *dg_sum += p.dg_g_or_c_init;
         }
     }
-    let mut sx = Vec::<char>::new();
-    for c in s.chars() {
-        sx.push(c);
-    }
     let mut b = Vec::<usize>::new();
     for i in 0..sx.len() {
         if sx[i] == 'A' {
@@ -443,9 +785,9 @@ pub fn thermodynamic_sums_dna(
         }
     }
     for i in 0..sx.len() - 1 {
-        *dh_sum += dh[b[i]][b[i + 1]];
-        *ds_sum += ds[b[i]][b[i + 1]];
-        *dg_sum += dg[b[i]][b[i + 1]];
+        *dh_sum += p.dh[b[i]][b[i + 1]];
+        *ds_sum += p.ds[b[i]][b[i + 1]];
+        *dg_sum += p.dg[b[i]][b[i + 1]];
     }
 
     // Handle locked bases.
@@ -466,26 +808,276 @@ pub fn thermodynamic_sums_dna(
         assert!(!locked[0] && !locked[1]);
         assert!(!locked[locked.len() - 1]);
         assert!(!locked[locked.len() - 2]);
-        let mut ddh_left = Vec::<Vec<f64>>::new();
-        let mut dds_left = Vec::<Vec<f64>>::new();
-        let mut ddg_left = Vec::<Vec<f64>>::new();
-        let mut ddh_right = Vec::<Vec<f64>>::new();
-        let mut dds_right = Vec::<Vec<f64>>::new();
-        let mut ddg_right = Vec::<Vec<f64>>::new();
-        get_locked_thermodynamic_parameters_dna(
-            &mut ddh_left,
-            &mut dds_left,
-            &mut ddg_left,
-            &mut ddh_right,
-            &mut dds_right,
-            &mut ddg_right,
-        );
+        let l = locked_thermodynamic_parameters();
         for i in 0..locked.len() {
             if locked[i] {
-                *dh_sum += ddh_left[b[i]][b[i + 1]] + ddh_right[b[i - 1]][b[i]];
-                *ds_sum += dds_left[b[i]][b[i + 1]] + dds_right[b[i - 1]][b[i]];
-                *dg_sum += ddg_left[b[i]][b[i + 1]] + ddg_right[b[i - 1]][b[i]];
+                *dh_sum += l.ddh_left[b[i]][b[i + 1]] + l.ddh_right[b[i - 1]][b[i]];
+                *ds_sum += l.dds_left[b[i]][b[i + 1]] + l.dds_right[b[i - 1]][b[i]];
+                *dg_sum += l.ddg_left[b[i]][b[i + 1]] + l.ddg_right[b[i - 1]][b[i]];
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p dna test_try_tm_nearest_neighbor
+
+    use crate::{
+        get_locked_thermodynamic_parameters_dna, get_thermodynamic_parameters_dna,
+        locked_thermodynamic_parameters, thermodynamic_parameters, thermodynamic_sums_dna,
+        tm_nearest_neighbor, tm_nearest_neighbor_batch, tm_nearest_neighbor_full,
+        tm_nearest_neighbor_full_salt, tm_range_iupac, to_matrix, try_tm_nearest_neighbor,
+        try_tm_nearest_neighbor_full, try_tm_range_iupac, try_verify_dna, try_verify_dna_iupac,
+        DnaError,
+    };
+
+    #[test]
+    fn test_try_tm_nearest_neighbor() {
+        assert_eq!(try_verify_dna("ACGT"), Ok(()));
+        assert_eq!(
+            try_verify_dna("ACNGTX"),
+            Err(DnaError::InvalidBase { base: 'N', pos: 2 })
+        );
+        let e = try_tm_nearest_neighbor("ACGTTGCAxG").unwrap_err();
+        assert_eq!(e, DnaError::InvalidBase { base: 'x', pos: 8 });
+        assert_eq!(
+            e.to_string(),
+            "invalid base 'x' at position 8: only A, C, G and T are allowed"
+        );
+        let s = "ACGTTGCATGCCAGTAGGCTA";
+        assert_eq!(try_tm_nearest_neighbor(s), Ok(tm_nearest_neighbor(s)));
+        let t = "ACGTT+GCATGCCAG+TAGGCTA";
+        assert_eq!(try_tm_nearest_neighbor(t), Ok(tm_nearest_neighbor(t)));
+        assert!(tm_nearest_neighbor(t) > tm_nearest_neighbor(s));
+    }
+
+    #[test]
+    fn test_try_tm_nearest_neighbor_full() {
+        let f = |s: &str, locked: &[bool]| {
+            try_tm_nearest_neighbor_full(s, 0.00000025, 0.05, locked)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(f("", &[]), "the sequence is empty");
+        assert_eq!(
+            f("AC+GT+N", &[]),
+            "invalid base 'N' at position 6: only A, C, G and T are allowed"
+        );
+        assert_eq!(f("ACG++TACG", &[]), "++ at position 3");
+        assert_eq!(f("ACGTACG+", &[]), "the sequence ends with +");
+        assert_eq!(
+            f("ACG+TACG", &[false; 7]),
+            "+ symbols may not be used if locked bases are also given"
+        );
+        assert_eq!(
+            f("ACG+T+ACG", &[]),
+            "locked bases at positions 3 and 4: two locked bases in a row are not allowed"
+        );
+        assert_eq!(
+            f("A+CGTACG", &[]),
+            "locked base at position 1: locked bases are not allowed at or next to the \
+             beginning or end"
+        );
+        let mut locked = [false; 9];
+        locked[6] = true;
+        assert_eq!(
+            f("ACGTACG", &locked),
+            "locked has length 9 but the sequence has length 7"
+        );
+        let locked = [false, false, false, false, false, true, false];
+        assert_eq!(
+            f("ACGTACG", &locked),
+            "locked base at position 5: locked bases are not allowed at or next to the \
+             beginning or end"
+        );
+
+        // Valid input gives the same answer with either way of specifying locked bases.
+
+        let locked = [false, false, true, false, false, true, false, false];
+        let tm = tm_nearest_neighbor_full("ACGTACGT", 0.00000025, 0.05, &locked);
+        assert_eq!(try_tm_nearest_neighbor("AC+GTA+CGT"), Ok(tm));
+        assert_eq!(
+            try_tm_nearest_neighbor_full("ACGTACGT", 0.00000025, 0.05, &[]),
+            Ok(tm_nearest_neighbor("ACGTACGT"))
+        );
+    }
+
+    #[test]
+    fn test_tm_range_iupac() {
+        let s = "ACGTTGCATGCCAGTAGGCTA";
+        let tm = tm_nearest_neighbor(s);
+        assert_eq!(tm_range_iupac(s), (tm, tm));
+        assert!(try_verify_dna_iupac("ACGTNRYSWKMBDHV").is_ok());
+        assert!(try_verify_dna_iupac("ACGTU").is_err());
+        let (min, max) = tm_range_iupac("ACGTTGCATGCSAGTAGGCTA");
+        let (tm_c, tm_g) = (
+            tm_nearest_neighbor("ACGTTGCATGCCAGTAGGCTA"),
+            tm_nearest_neighbor("ACGTTGCATGCGAGTAGGCTA"),
+        );
+        assert_eq!((min, max), (tm_c.min(tm_g), tm_c.max(tm_g)));
+        let (min, max) = tm_range_iupac("ACGTTGCANGCCAGTAGGCWA");
+        assert!(min < tm && tm < max);
+        assert_eq!(
+            tm_range_iupac("ACGTT+GCANGCC"),
+            try_tm_range_iupac("ACGTT+GCANGCC", 4).unwrap()
+        );
+        assert_eq!(
+            try_tm_range_iupac("ACGTNNNNNNNNACGT", 1000),
+            Err(DnaError::TooManyResolutions { max: 1000 })
+        );
+        assert_eq!(
+            try_tm_range_iupac("ACGTXACGT", 1000),
+            Err(DnaError::InvalidBase { base: 'X', pos: 4 })
+        );
+    }
+
+    #[test]
+    fn test_tm_nearest_neighbor_batch() {
+        // Generate a few thousand pseudorandom sequences, some with locked bases.
+
+        let mut seqs = Vec::<String>::new();
+        let mut r = 1_u64;
+        for i in 0..3000 {
+            let mut s = String::new();
+            for j in 0..18 + i % 8 {
+                r = r
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                if i % 10 == 0 && j == 8 {
+                    s.push('+');
+                }
+                s.push(b"ACGT"[(r >> 62) as usize] as char);
+            }
+            seqs.push(s);
+        }
+        let seqs = seqs.iter().map(String::as_str).collect::<Vec<_>>();
+        let tms = tm_nearest_neighbor_batch(&seqs, 0.00000025, 0.05);
+        assert_eq!(tms.len(), seqs.len());
+        for (s, &tm) in seqs.iter().zip(tms.iter()) {
+            assert_eq!(tm, tm_nearest_neighbor(s));
+        }
+        assert!(tm_nearest_neighbor_batch(&[], 0.00000025, 0.05).is_empty());
+    }
+
+    #[test]
+    fn test_thermodynamic_parameters() {
+        // The cached parameters agree with those returned by the public functions.
+
+        let (mut dh, mut ds, mut dg) = (Vec::new(), Vec::new(), Vec::new());
+        let mut x = [0.0; 9];
+        let [a, b, c, d, e, f, g, h, i] = &mut x;
+        get_thermodynamic_parameters_dna(&mut dh, &mut ds, &mut dg, a, b, c, d, e, f, g, h, i);
+        let p = thermodynamic_parameters();
+        assert_eq!(
+            [p.dh, p.ds, p.dg],
+            [to_matrix(&dh), to_matrix(&ds), to_matrix(&dg)]
+        );
+        assert_eq!(
+            x,
+            [
+                p.dh_g_or_c_init,
+                p.dh_a_or_t_init,
+                p.ds_g_or_c_init,
+                p.ds_a_or_t_init,
+                p.dg_g_or_c_init,
+                p.dg_a_or_t_init,
+                p.dh_symmetry_correction,
+                p.ds_symmetry_correction,
+                p.dg_symmetry_correction
+            ]
+        );
+        let mut y = vec![Vec::new(); 6];
+        if let [a, b, c, d, e, f] = &mut y[..] {
+            get_locked_thermodynamic_parameters_dna(a, b, c, d, e, f);
+        }
+        let l = locked_thermodynamic_parameters();
+        assert_eq!(
+            [
+                l.ddh_left,
+                l.dds_left,
+                l.ddg_left,
+                l.ddh_right,
+                l.dds_right,
+                l.ddg_right
+            ],
+            [
+                to_matrix(&y[0]),
+                to_matrix(&y[1]),
+                to_matrix(&y[2]),
+                to_matrix(&y[3]),
+                to_matrix(&y[4]),
+                to_matrix(&y[5])
+            ]
+        );
+
+        // Melting temperatures and sums are unchanged from when the parameters were
+        // recomputed on each call.
+
+        // test 1
+        assert_eq!(
+            tm_nearest_neighbor("ACGTTGCATGCCAGTAGGCTA"),
+            59.208015248418064
+        );
+        // test 2
+        assert_eq!(tm_nearest_neighbor("AC+GTA+CGT"), 23.792297549680598);
+        // test 3
+        assert_eq!(tm_nearest_neighbor("GGGCCCAAATTT"), 38.48724778815813);
+        // test 4
+        assert_eq!(
+            tm_nearest_neighbor_full_salt(
+                "ACGTTGCATGCCAGTAGGCTA",
+                0.00000025,
+                0.05,
+                0.0015,
+                0.0008,
+                &[]
+            ),
+            64.34860594206248
+        );
+        // test 5
+        let mut locked = vec![false; 21];
+        locked[3] = true;
+        locked[18] = true;
+        let (mut dh_sum, mut ds_sum, mut dg_sum) = (0.0, 0.0, 0.0);
+        thermodynamic_sums_dna(
+            "ACGTTGCATGCCAGTAGGCTA",
+            &mut dh_sum,
+            &mut ds_sum,
+            &mut dg_sum,
+            true,
+            true,
+            &locked,
+        );
+        assert_eq!(
+            (dh_sum, ds_sum, dg_sum),
+            (-162.82400000000004, -431.5799999999999, -29.009060000000016)
+        );
+    }
+
+    #[test]
+    fn test_tm_nearest_neighbor_full_salt() {
+        let s = "ACGTTGCATGCCAGTAGGCTA";
+        let (s_mol, na_mol) = (0.00000025, 0.05);
+        let tm = tm_nearest_neighbor_full(s, s_mol, na_mol, &[]);
+        let salt = |mg, dntp| tm_nearest_neighbor_full_salt(s, s_mol, na_mol, mg, dntp, &[]);
+
+        // Without free Mg2+, or with very little of it, the Na+ correction is used.
+
+        assert_eq!(salt(0.0, 0.0), tm);
+        assert_eq!(salt(0.0015, 0.01), tm);
+        assert_eq!(salt(0.000001, 0.0), tm);
+
+        // Adding Mg2+ raises the melting temperature, and adding dNTPs lowers it again.  Once
+        // there is enough Mg2+, it dominates the effect of Na+.
+
+        let tm_mg = salt(0.0015, 0.0);
+        assert!(tm_mg > tm + 2.0 && tm_mg < tm + 15.0);
+        assert!(salt(0.0015, 0.0008) < tm_mg);
+        assert!(salt(0.003, 0.0) > tm_mg);
+        let tm_mg_only = tm_nearest_neighbor_full_salt(s, s_mol, 0.0, 0.0015, 0.0, &[]);
+        assert!((tm_mg_only - tm_mg).abs() < 1.0);
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+// Primer quality checks: estimate the stability of the most stable hairpin of a primer, and of
+// the most stable duplex formed by two copies of it (a self-dimer).  Both are given as free
+// energies dG at 37 degrees (kcal/mole), computed using the nearest-neighbor parameters of
+// thermodynamic_sums_dna.  More negative values are worse, and zero means that no structure
+// was found.
+//
+// These are rough screens, not a substitute for a full secondary structure calculation.  The
+// simplifying assumptions are:
+// • only a single stretch of perfectly complementary base pairs is considered, so structures
+//   with mismatches, bulges or internal loops are ignored, as are dangling ends;
+// • a stretch must consist of at least two base pairs;
+// • for a hairpin, the loop must have at least three bases, and its free energy is taken from
+//   the hairpin loop table in SantaLucia and Hicks, Annu. Rev. Biophys. Biomol. Struct. 33
+//   (2004), 415-440, extrapolated for long loops, ignoring the loop sequence;
+// • for a self-dimer, initiation terms are included, and a symmetry correction is applied if
+//   the stretch is self-complementary.
+
+use crate::{seq::rc, thermodynamic_sums_dna, verify_dna};
+
+// Find the maximal stretches of complementary bases between a sequence s and itself, in
+// antiparallel orientation.  Each stretch is returned as (i, j, len), meaning that s[i + m]
+// pairs with s[j - m] for 0 <= m < len.  Each stretch is found twice, once with i <= j and
+// once with i and j swapped.
+
+fn complementary_stretches(s: &[u8]) -> Vec<(usize, usize, usize)> {
+    let n = s.len();
+    let r = rc(s);
+    let mut stretches = Vec::<(usize, usize, usize)>::new();
+    if n == 0 {
+        return stretches;
+    }
+
+    // Base s[i] pairs with s[j] if s[i] = r[n - 1 - j].  Traverse the diagonals of s versus r,
+    // finding maximal runs of agreement.
+
+    for d in 0..2 * n - 1 {
+        let (mut i, mut q) = if d < n {
+            (0, n - 1 - d)
+        } else {
+            (d - (n - 1), 0)
+        };
+        let mut len = 0;
+        while i < n && q < n {
+            if s[i] == r[q] {
+                len += 1;
+            } else if len > 0 {
+                stretches.push((i - len, n - 1 - (q - len), len));
+                len = 0;
+            }
+            i += 1;
+            q += 1;
+        }
+        if len > 0 {
+            stretches.push((i - len, n - 1 - (q - len), len));
+        }
+    }
+    stretches
+}
+
+fn dg_of(s: &[u8], include_symmetry_correction: bool, include_initiation_terms: bool) -> f64 {
+    let (mut dh, mut ds, mut dg) = (0.0, 0.0, 0.0);
+    thermodynamic_sums_dna(
+        std::str::from_utf8(s).unwrap(),
+        &mut dh,
+        &mut ds,
+        &mut dg,
+        include_symmetry_correction,
+        include_initiation_terms,
+        &[],
+    );
+    dg
+}
+
+// Free energy of a hairpin loop having n bases, n >= 3.
+
+fn hairpin_loop_dg(n: usize) -> f64 {
+    const LOOP_DG: [f64; 7] = [3.5, 3.5, 3.3, 4.0, 4.2, 4.3, 4.5];
+    assert!(n >= 3);
+    if n <= 9 {
+        LOOP_DG[n - 3]
+    } else {
+        // Jacobson-Stockmayer extrapolation, using RT = 0.616 kcal/mole at 37 degrees.
+        LOOP_DG[6] + 2.44 * 0.616 * (n as f64 / 9.0).ln()
+    }
+}
+
+// Estimate the free energy of the most stable hairpin of a DNA sequence.
+
+pub fn hairpin_score(s: &str) -> f64 {
+    verify_dna(s);
+    let x = s.as_bytes();
+    let mut best = 0.0_f64;
+    for (i, j, len) in complementary_stretches(x) {
+        // Shorten the stem if needed so that the loop has at least three bases.
+
+        if j <= i {
+            continue;
+        }
+        let mut len = len;
+        while len > 0 && j + 1 - len < i + len + 3 {
+            len -= 1;
+        }
+        if len < 2 {
+            continue;
+        }
+        let loop_len = j + 1 - len - (i + len);
+        let dg = dg_of(&x[i..i + len], false, false) + hairpin_loop_dg(loop_len);
+        best = best.min(dg);
+    }
+    best
+}
+
+// Estimate the free energy of the most stable duplex formed by two copies of a DNA sequence.
+
+pub fn self_dimer_score(s: &str) -> f64 {
+    verify_dna(s);
+    let x = s.as_bytes();
+    let mut best = 0.0_f64;
+    for (i, _, len) in complementary_stretches(x) {
+        if len < 2 {
+            continue;
+        }
+        let stretch = &x[i..i + len];
+        let symmetric = *stretch == *rc(stretch);
+        best = best.min(dg_of(stretch, symmetric, true));
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p dna test_primer
+
+    use crate::primer::{complementary_stretches, hairpin_score, rc, self_dimer_score};
+
+    #[test]
+    fn test_primer_complementary_stretches() {
+        // GAATTC is its own reverse complement, so the whole thing pairs with itself.
+
+        let s = b"GAATTC";
+        let x = complementary_stretches(s);
+        assert!(x.contains(&(0, 5, 6)));
+        for (i, j, len) in x {
+            for m in 0..len {
+                assert_eq!(s[i + m], rc(&[s[j - m]])[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_primer_scores() {
+        // Hairpin: stem CCCCGG, loop AAAA.
+
+        let hairpin = "CCCCGGAAAACCGGGG";
+        let weak = "CCCCGGAAAATTTTTT";
+        assert!(hairpin_score(hairpin) < -3.0);
+        assert!(hairpin_score(hairpin) < hairpin_score(weak));
+        assert_eq!(hairpin_score("ACCAAA"), 0.0);
+
+        // Self-dimer: a palindromic primer versus one with no complementary stretches.
+
+        let palindrome = "ACGTACGTACGTACGT";
+        let plain = "AAAAACCCCCAAAAAC";
+        assert!(self_dimer_score(palindrome) < -10.0);
+        assert_eq!(self_dimer_score(plain), 0.0);
+        assert!(self_dimer_score(palindrome) < self_dimer_score("ACGTACGTTTTTTTTT"));
+    }
+}
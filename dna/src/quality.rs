@@ -0,0 +1,81 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+// Phred quality score utilities, as used in filtering low-quality reads before alignment.
+//
+// A Phred quality score Q corresponds to an error probability p = 10^(-Q/10), so e.g.
+// Q30 means a 1 in 1000 chance that the base call is wrong.  In FASTQ files, quality
+// scores are stored as ASCII characters, obtained by adding an offset to Q.  The modern
+// (Sanger/Illumina 1.8+) convention is Phred+33, and this is assumed by default.  Older
+// Illumina files used Phred+64, which can be handled by passing that offset explicitly.
+
+// The default offset between an ASCII-encoded quality character and its Phred score.
+
+pub const PHRED_OFFSET: u8 = 33;
+
+// Convert a Phred quality score (not ASCII-encoded) to an error probability.
+
+pub fn phred_to_prob(q: u8) -> f64 {
+    10.0_f64.powf(-(q as f64) / 10.0)
+}
+
+// Convert an error probability to the nearest Phred quality score.  The result is capped
+// at u8::MAX, which is also what is returned for p = 0.
+
+pub fn prob_to_phred(p: f64) -> u8 {
+    assert!((0.0..=1.0).contains(&p));
+    let q = (-10.0 * p.log10()).round();
+    if q >= u8::MAX as f64 {
+        u8::MAX
+    } else {
+        q as u8
+    }
+}
+
+// Compute the mean Phred score of a vector of ASCII-encoded quality scores, assuming the
+// Phred+33 encoding.  Note that this is the mean of the quality scores, not the quality score
+// of the mean error probability.  Return 0 for empty input.
+
+pub fn mean_quality(quals: &[u8]) -> f64 {
+    mean_quality_with_offset(quals, PHRED_OFFSET)
+}
+
+// Same as mean_quality, but using a given offset (e.g. 64 for old Illumina files).
+
+pub fn mean_quality_with_offset(quals: &[u8], offset: u8) -> f64 {
+    if quals.is_empty() {
+        return 0.0;
+    }
+    let mut sum = 0_u64;
+    for &q in quals {
+        assert!(
+            q >= offset,
+            "quality character {} is below offset {}",
+            q,
+            offset
+        );
+        sum += (q - offset) as u64;
+    }
+    sum as f64 / quals.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p dna test_phred
+
+    use crate::quality::{mean_quality, mean_quality_with_offset, phred_to_prob, prob_to_phred};
+
+    #[test]
+    fn test_phred() {
+        assert!((phred_to_prob(30) - 0.001).abs() < 1e-12);
+        assert_eq!(prob_to_phred(0.001), 30);
+        for q in 0..=60 {
+            assert_eq!(prob_to_phred(phred_to_prob(q)), q);
+        }
+        assert_eq!(prob_to_phred(0.0), u8::MAX);
+        assert_eq!(mean_quality(b"??II"), 35.0);
+        assert_eq!(mean_quality_with_offset(b"^^hh", 64), 35.0);
+        assert_eq!(mean_quality(b""), 0.0);
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright (c) 2020 10X Genomics, Inc. All rights reserved.
+
+// Basic operations on DNA sequences.
+
+use crate::{verify_dna, DnaError};
+
+// Reverse complement a DNA sequence consisting of A, C, G and T.
+
+pub(crate) fn rc(s: &[u8]) -> Vec<u8> {
+    s.iter()
+        .rev()
+        .map(|c| match c {
+            b'A' => b'T',
+            b'C' => b'G',
+            b'G' => b'C',
+            _ => b'A',
+        })
+        .collect()
+}
+
+// Reverse complement a DNA sequence.  The sequence must consist of A, C, G and T only.
+
+pub fn reverse_complement(s: &str) -> String {
+    verify_dna(s);
+    String::from_utf8(rc(s.as_bytes())).unwrap()
+}
+
+// Same as reverse_complement, but also allow lowercase (e.g. soft-masked) bases, whose case is
+// preserved.  The panicking version is revcomp, and try_revcomp instead returns an error naming
+// the first character that is not one of A, C, G or T, in either case.
+
+pub fn revcomp(s: &str) -> String {
+    try_revcomp(s).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn try_revcomp(s: &str) -> Result<String, DnaError> {
+    let mut r = Vec::<u8>::with_capacity(s.len());
+    for (pos, base) in s.chars().enumerate() {
+        let c = match base {
+            'A' => b'T',
+            'C' => b'G',
+            'G' => b'C',
+            'T' => b'A',
+            'a' => b't',
+            'c' => b'g',
+            'g' => b'c',
+            't' => b'a',
+            _ => return Err(DnaError::InvalidBase { base, pos }),
+        };
+        r.push(c);
+    }
+    r.reverse();
+    Ok(String::from_utf8(r).unwrap())
+}
+
+// Return the bases denoted by an IUPAC nucleotide code, e.g. R denotes A or G, and N denotes any
+// base, or None if the character is not such a code.
+
+pub fn iupac_bases(c: char) -> Option<&'static [u8]> {
+    let bases: &[u8] = match c {
+        'A' => b"A",
+        'C' => b"C",
+        'G' => b"G",
+        'T' => b"T",
+        'R' => b"AG",
+        'Y' => b"CT",
+        'S' => b"CG",
+        'W' => b"AT",
+        'K' => b"GT",
+        'M' => b"AC",
+        'B' => b"CGT",
+        'D' => b"AGT",
+        'H' => b"ACT",
+        'V' => b"ACG",
+        'N' => b"ACGT",
+        _ => return None,
+    };
+    Some(bases)
+}
+
+// Compute the fraction of bases in a DNA sequence that are G or C, or zero for an empty
+// sequence.  The sequence must consist of A, C, G and T only.
+
+pub fn gc_content(s: &str) -> f64 {
+    verify_dna(s);
+    if s.is_empty() {
+        return 0.0;
+    }
+    let gc = s.bytes().filter(|&c| c == b'G' || c == b'C').count();
+    gc as f64 / s.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p dna test_seq
+
+    use crate::seq::{gc_content, iupac_bases, revcomp, reverse_complement, try_revcomp};
+    use crate::DnaError;
+
+    #[test]
+    fn test_seq() {
+        assert_eq!(reverse_complement("AACGTG"), "CACGTT");
+        assert_eq!(reverse_complement(""), "");
+        for s in ["GAATTC", "ACGT", "GGCC"].iter() {
+            assert_eq!(reverse_complement(s), *s);
+        }
+        let s = "ATTGCCGTA";
+        assert_eq!(reverse_complement(&reverse_complement(s)), s);
+        assert_eq!(gc_content("GAATTC"), 2.0 / 6.0);
+        assert_eq!(gc_content("GGCC"), 1.0);
+        assert_eq!(gc_content("ATAT"), 0.0);
+        assert_eq!(gc_content(""), 0.0);
+        assert_eq!(gc_content(s), gc_content(&reverse_complement(s)));
+        assert_eq!(revcomp("ACGT"), "ACGT");
+        assert_eq!(revcomp("AACGTG"), reverse_complement("AACGTG"));
+        assert_eq!(revcomp("ACgtta"), "taacGT");
+        assert_eq!(
+            try_revcomp("ACNT"),
+            Err(DnaError::InvalidBase { base: 'N', pos: 2 })
+        );
+        assert_eq!(iupac_bases('Y'), Some(&b"CT"[..]));
+        assert_eq!(iupac_bases('N').unwrap().len(), 4);
+        assert_eq!(iupac_bases('X'), None);
+    }
+}
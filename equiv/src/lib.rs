@@ -16,6 +16,7 @@
 // disjoint-sets crate at the end of this file.  The implementations in other
 // crates were not tested.
 
+use std::convert::TryFrom;
 use std::mem::swap;
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -29,12 +30,14 @@ use std::mem::swap;
 // - time to find all orbit reps = O(N)
 // - time to find an orbit = O(size of orbit)
 // - time to find the size of an orbit = O(1)
+// - time to find the number of orbits = O(1)
 // - time to find the class id of an element = O(1).
 
 pub struct EquivRel {
     x: Vec<i32>, // next element in orbit
     y: Vec<i32>, // orbit class id
     z: Vec<i32>, // orbit size
+    norbits: usize,
 }
 
 impl EquivRel {
@@ -51,14 +54,47 @@ impl EquivRel {
             x: xx,
             y: yy,
             z: zz,
+            norbits: n as usize,
         }
     }
 
+    // with_len: same as new, but taking a usize.  This and the other _usize functions allow
+    // the relation to be used with usize indices, without casting.
+
+    pub fn with_len(n: usize) -> EquivRel {
+        EquivRel::new(i32::try_from(n).expect("with_len: too many elements"))
+    }
+
+    // from_joins: create an equivalence relation on n elements and apply the given joins, in
+    // order.  The result is the same as for applying the joins one at a time.
+
+    pub fn from_joins(n: i32, joins: &[(i32, i32)]) -> EquivRel {
+        EquivRel::from_joins_iter(n, joins.iter().copied())
+    }
+
+    // from_joins_iter: same as from_joins, but taking any iterator over the joins, without first
+    // collecting them.
+
+    pub fn from_joins_iter<I: IntoIterator<Item = (i32, i32)>>(n: i32, joins: I) -> EquivRel {
+        let mut e = EquivRel::new(n);
+        for (a, b) in joins {
+            e.join(a, b);
+        }
+        e
+    }
+
     pub fn from_raw(xx: Vec<i32>, yy: Vec<i32>, zz: Vec<i32>) -> EquivRel {
+        let mut norbits = 0;
+        for i in 0..yy.len() {
+            if i == yy[i] as usize {
+                norbits += 1;
+            }
+        }
         EquivRel {
             x: xx,
             y: yy,
             z: zz,
+            norbits,
         }
     }
 
@@ -89,6 +125,30 @@ impl EquivRel {
             // Update orbit size.
 
             self.z[self.y[bx as usize] as usize] = new_size;
+            self.norbits -= 1;
+        }
+    }
+
+    pub fn join_usize(&mut self, a: usize, b: usize) {
+        let a = i32::try_from(a).expect("join_usize: element out of range");
+        let b = i32::try_from(b).expect("join_usize: element out of range");
+        self.join(a, b);
+    }
+
+    // join_all: apply all the joins of another equivalence relation on a set of the same size,
+    // so that the orbits become the unions of overlapping orbits of the two.  This allows
+    // equivalence relations to be built in pieces, e.g. in parallel, and then combined.
+
+    pub fn join_all(&mut self, other: &EquivRel) {
+        assert_eq!(
+            self.x.len(),
+            other.x.len(),
+            "join_all: the equivalence relations have different sizes"
+        );
+        for o in other.orbits() {
+            for &b in o[1..].iter() {
+                self.join(o[0], b);
+            }
         }
     }
 
@@ -102,13 +162,7 @@ impl EquivRel {
     }
 
     pub fn norbits(&self) -> usize {
-        let mut n = 0;
-        for i in 0..self.x.len() {
-            if i == self.y[i] as usize {
-                n += 1;
-            }
-        }
-        n
+        self.norbits
     }
 
     pub fn orbit_size(&self, a: i32) -> i32 {
@@ -133,9 +187,50 @@ impl EquivRel {
         }
     }
 
+    // orbits: iterate over all the orbits, ordered by their class ids, which are the orbit
+    // reps.  This is equivalent to calling orbit_reps, and then orbit for each rep, but
+    // makes a single pass through the elements.
+
+    pub fn orbits(&self) -> impl Iterator<Item = Vec<i32>> + '_ {
+        (0..self.x.len() as i32)
+            .filter(move |&a| self.y[a as usize] == a)
+            .map(move |a| {
+                let mut o = Vec::<i32>::new();
+                self.orbit(a, &mut o);
+                o
+            })
+    }
+
+    // set_orbits: set orbits to the orbits, in the same order as orbits(), each in the order
+    // given by orbit.
+
+    pub fn set_orbits(&self, orbits: &mut Vec<Vec<i32>>) {
+        orbits.clear();
+        orbits.extend(self.orbits());
+    }
+
+    // orbit_usize: compute the orbit o of an element, as a Vec<usize>.
+
+    pub fn orbit_usize(&self, a: usize, o: &mut Vec<usize>) {
+        o.clear();
+        o.push(a);
+        let mut b = a;
+        loop {
+            b = self.x[b] as usize;
+            if b == a {
+                break;
+            }
+            o.push(b);
+        }
+    }
+
     pub fn class_id(&self, a: i32) -> i32 {
         self.y[a as usize]
     }
+
+    pub fn class_id_usize(&self, a: usize) -> usize {
+        self.y[a] as usize
+    }
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -280,3 +375,138 @@ impl EquivRel {
 //
 // Of course one ought to be able to define a reproducible test that exhibits this
 // performance difference.
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p equiv
+
+    use crate::EquivRel;
+
+    // Count the orbits by scanning, to check norbits.
+
+    fn norbits_scan(e: &EquivRel) -> usize {
+        let mut n = 0;
+        for i in 0..e.x.len() {
+            if i == e.y[i] as usize {
+                n += 1;
+            }
+        }
+        n
+    }
+
+    // Return the sorted orbits of an equivalence relation, each of which is sorted.
+
+    fn sorted_orbits(e: &EquivRel) -> Vec<Vec<i32>> {
+        let mut reps = Vec::<i32>::new();
+        e.orbit_reps(&mut reps);
+        let mut orbits = Vec::<Vec<i32>>::new();
+        for r in reps {
+            let mut o = Vec::<i32>::new();
+            e.orbit(r, &mut o);
+            o.sort_unstable();
+            orbits.push(o);
+        }
+        orbits.sort();
+        orbits
+    }
+
+    #[test]
+    fn test_from_joins() {
+        let n = 1000;
+        let mut joins = Vec::<(i32, i32)>::new();
+        for i in 0..600 {
+            joins.push(((i * 7919) % n, (i * 104729 + 13) % n));
+        }
+        let mut e = EquivRel::new(n);
+        for &(a, b) in joins.iter() {
+            e.join(a, b);
+            assert_eq!(e.norbits(), norbits_scan(&e));
+        }
+        let f = EquivRel::from_joins(n, &joins);
+        assert_eq!((&f.x, &f.y, &f.z), (&e.x, &e.y, &e.z));
+        assert_eq!(e.norbits(), f.norbits());
+        let g = EquivRel::from_joins_iter(n, joins.iter().copied());
+        assert_eq!((&g.x, &g.y, &g.z), (&e.x, &e.y, &e.z));
+        assert_eq!(g.norbits(), e.norbits());
+    }
+
+    #[test]
+    fn test_usize() {
+        let n = 1000;
+        let mut e = EquivRel::with_len(n);
+        let mut f = EquivRel::new(n as i32);
+        for i in 0..600 {
+            let (a, b) = ((i * 7919) % n, (i * 104729 + 13) % n);
+            e.join_usize(a, b);
+            f.join(a as i32, b as i32);
+        }
+        assert_eq!(sorted_orbits(&e), sorted_orbits(&f));
+        let mut o = Vec::<usize>::new();
+        for a in 0..n {
+            assert_eq!(e.class_id_usize(a), f.class_id(a as i32) as usize);
+            e.orbit_usize(a, &mut o);
+            assert!(o
+                .iter()
+                .all(|&b| e.class_id_usize(b) == e.class_id_usize(a)));
+            assert_eq!(o.len(), e.orbit_size(a as i32) as usize);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "join_usize: element out of range")]
+    fn test_join_usize_out_of_range() {
+        let mut e = EquivRel::with_len(10);
+        e.join_usize(1, usize::MAX);
+    }
+
+    #[test]
+    fn test_orbits() {
+        let e = EquivRel::from_joins(8, &[(0, 3), (3, 5), (1, 6), (6, 1)]);
+        let mut orbits = e.orbits().collect::<Vec<_>>();
+        assert_eq!(orbits.len(), e.norbits());
+        for o in orbits.iter_mut() {
+            o.sort_unstable();
+        }
+        orbits.sort();
+        assert_eq!(
+            orbits,
+            vec![vec![0, 3, 5], vec![1, 6], vec![2], vec![4], vec![7]]
+        );
+        assert_eq!(orbits, sorted_orbits(&e));
+        assert_eq!(EquivRel::new(0).orbits().count(), 0);
+
+        // The elements of each orbit are in the order given by orbit.
+
+        let mut all = vec![vec![9]];
+        e.set_orbits(&mut all);
+        assert_eq!(all, e.orbits().collect::<Vec<_>>());
+        let mut o = Vec::<i32>::new();
+        for x in all.iter() {
+            e.orbit(x[0], &mut o);
+            assert_eq!(*x, o);
+        }
+    }
+
+    #[test]
+    fn test_join_all() {
+        let n = 500;
+        let mut joins = Vec::<(i32, i32)>::new();
+        for i in 0..300 {
+            joins.push(((i * 7919) % n, (i * 104729 + 13) % n));
+        }
+        let all = EquivRel::from_joins(n, &joins);
+        let mut e = EquivRel::from_joins(n, &joins[0..100]);
+        for shard in joins[100..].chunks(70) {
+            e.join_all(&EquivRel::from_joins(n, shard));
+        }
+        assert_eq!(sorted_orbits(&e), sorted_orbits(&all));
+        for a in 0..n {
+            assert_eq!(e.orbit_size(a), all.orbit_size(a));
+        }
+        assert_eq!(e.norbits(), norbits_scan(&e));
+        let r = EquivRel::from_raw(e.x.clone(), e.y.clone(), e.z.clone());
+        assert_eq!(r.norbits(), e.norbits());
+    }
+}
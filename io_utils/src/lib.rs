@@ -5,11 +5,20 @@
 use bincode::{deserialize_from, serialize_into};
 use flate2::read::MultiGzDecoder;
 use serde::{de::DeserializeOwned, Serialize};
-use std::ffi::OsStr;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::{fmt::Debug, fs::File, io::prelude::*, path::Path};
-use string_utils::TextUtils;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::prelude::*,
+    path::{Path, PathBuf},
+};
+
+// Reexported for use by the open_for_write_gz_new! macro.
+
+pub use flate2;
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // GET CONTENTS OF DIRECTORY
@@ -26,6 +35,66 @@ pub fn dir_list(d: &str) -> Vec<String> {
     y
 }
 
+// walk_dir: recursively find all paths under a directory that pass a filter, and return
+// them, sorted.  The filter is applied to every entry, including directories, but all
+// directories are descended into regardless of whether they pass.  Symbolic links are not
+// followed (but are returned if they pass the filter), so symlink loops are not a problem.
+// Directories that cannot be read, e.g. because of permissions, are silently skipped.
+// Use walk_dir_verbose to instead get a warning for each of these.
+
+pub fn walk_dir(root: impl AsRef<Path>, filter: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut paths = Vec::<PathBuf>::new();
+    walk_dir_core(root.as_ref(), &filter, false, &mut paths);
+    paths.sort();
+    paths
+}
+
+pub fn walk_dir_verbose(root: impl AsRef<Path>, filter: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut paths = Vec::<PathBuf>::new();
+    walk_dir_core(root.as_ref(), &filter, true, &mut paths);
+    paths.sort();
+    paths
+}
+
+fn walk_dir_core(d: &Path, filter: &dyn Fn(&Path) -> bool, warn: bool, paths: &mut Vec<PathBuf>) {
+    let x = match fs::read_dir(d) {
+        Err(err) => {
+            if warn {
+                eprintln!(
+                    "WARNING: skipping directory {}: {}",
+                    d.to_string_lossy(),
+                    err
+                );
+            }
+            return;
+        }
+        Ok(x) => x,
+    };
+    for f in x {
+        let f = match f {
+            Err(err) => {
+                if warn {
+                    eprintln!(
+                        "WARNING: skipping entry in {}: {}",
+                        d.to_string_lossy(),
+                        err
+                    );
+                }
+                continue;
+            }
+            Ok(f) => f,
+        };
+        let p = f.path();
+        if filter(&p) {
+            paths.push(p.clone());
+        }
+        // Note that file_type does not follow symbolic links.
+        if f.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            walk_dir_core(&p, filter, warn, paths);
+        }
+    }
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // TEST FOR EXISTENCE OF FILE
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -34,6 +103,26 @@ pub fn path_exists(p: impl AsRef<Path>) -> bool {
     p.as_ref().exists()
 }
 
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+// CREATE DIRECTORIES
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Create a directory, and any missing parent directories, like mkdir -p.  It is not an error
+// if the directory already exists.  mkdir_p_result returns an error message that includes the
+// path on failure, and mkdir_p_safe instead panics with that message.
+
+pub fn mkdir_p_result(path: impl AsRef<Path>) -> Result<(), String> {
+    let path = path.as_ref();
+    fs::create_dir_all(path)
+        .map_err(|e| format!("Could not create directory \"{}\": {}.", path.display(), e))
+}
+
+pub fn mkdir_p_safe(path: impl AsRef<Path>) {
+    if let Err(e) = mkdir_p_result(path) {
+        panic!("{}", e);
+    }
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // WRITE STUFF
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -119,20 +208,151 @@ macro_rules! open_for_write_new {
     };
 }
 
+// open_for_write_gz_new! is like open_for_write_new!, but the output is gzipped.  The
+// compression is finished when the writer is dropped, and any error at that point is ignored,
+// so if that matters, call into_inner() and then finish() on the encoder.
+
+#[macro_export]
+macro_rules! open_for_write_gz_new {
+    ($filename:expr) => {
+        ::std::io::BufWriter::new($crate::flate2::write::GzEncoder::new(
+            ::std::fs::File::create(::core::convert::AsRef::<::std::path::Path>::as_ref(
+                $filename,
+            ))
+            .unwrap_or_else(|_| {
+                panic!(
+                    "Could not create file \"{}\"",
+                    ::core::convert::AsRef::<::std::path::Path>::as_ref($filename)
+                        .to_string_lossy()
+                )
+            }),
+            $crate::flate2::Compression::default(),
+        ))
+    };
+}
+
+// write_lines_gz( f, lines ): write lines to the gzipped file f, each followed by a newline.
+
+pub fn write_lines_gz(f: impl AsRef<Path>, lines: &[String]) {
+    let f = f.as_ref();
+    let mut out = open_for_write_gz_new![f];
+    lines
+        .iter()
+        .try_for_each(|line| writeln!(out, "{}", line))
+        .and_then(|_| {
+            out.into_inner()
+                .map_err(std::io::IntoInnerError::into_error)
+        })
+        .and_then(flate2::write::GzEncoder::finish)
+        .unwrap_or_else(|e| panic!("Failed to write file \"{}\": {}", f.to_string_lossy(), e));
+}
+
+// open_for_write_atomic( f ): open a file for writing, so that the file f either does not
+// change, or has exactly the content written.  This writes to the file <f>.tmp.<pid>.<n> in the
+// same directory, where n counts the calls in this process, so that concurrent writers to f
+// don't share a temporary file, and commit() renames it to f.  If the AtomicWriter is dropped without
+// calling commit(), e.g. because of a panic while writing, the temporary file is deleted and f
+// is unchanged.  The rename is only atomic if both files are on the same filesystem, which is
+// why the temporary file is a sibling of f.
+
+pub struct AtomicWriter {
+    out: Option<BufWriter<File>>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+pub fn open_for_write_atomic(f: impl AsRef<Path>) -> AtomicWriter {
+    let path = f.as_ref().to_path_buf();
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let n = COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    name.push(format!(".tmp.{}.{}", std::process::id(), n));
+    let tmp = path.with_file_name(name);
+    let out = File::create(&tmp)
+        .unwrap_or_else(|_| panic!("Could not create file \"{}\"", tmp.to_string_lossy()));
+    AtomicWriter {
+        out: Some(BufWriter::new(out)),
+        tmp,
+        path,
+    }
+}
+
+impl AtomicWriter {
+    // Finish writing and move the file into place.
+
+    pub fn commit(mut self) {
+        let out = self.out.take().unwrap();
+        out.into_inner()
+            .map_err(std::io::IntoInnerError::into_error)
+            .and_then(|f| f.sync_all())
+            .and_then(|_| fs::rename(&self.tmp, &self.path))
+            .unwrap_or_else(|e| {
+                let _ = fs::remove_file(&self.tmp);
+                panic!(
+                    "Failed to write file \"{}\": {}",
+                    self.path.to_string_lossy(),
+                    e
+                )
+            });
+    }
+}
+
+impl Write for AtomicWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.out.as_mut().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for AtomicWriter {
+    fn drop(&mut self) {
+        if self.out.take().is_some() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
 pub fn open_lz4<P: AsRef<Path>>(filename: P) -> lz4::Decoder<File> {
     let f = File::open(filename).expect("Failed to open file for reading");
     lz4::Decoder::new(f).expect("Failed to create lz4 decoder")
 }
 
-// If you accidentally pass a gzipped file to this it will succeed in opening the file,
-// but then when you try to run read_line, the read will return !is_ok().  This seems horrible.
+// Open a file for reading, decompressing it if it is lz4, gzip or zstd compressed.  This used
+// to go by the extension of the file, so that a compressed file having some other extension
+// would be opened raw, and then fail on read_line.  Now it is the same as open_auto, which
+// goes by the magic bytes at the start of the file.
 
 pub fn open_maybe_compressed<P: AsRef<Path>>(filename: P) -> Box<dyn Read> {
-    match filename.as_ref().extension().and_then(OsStr::to_str) {
-        Some("lz4") => Box::new(open_lz4(filename)) as Box<dyn Read>,
-        _ => Box::new(File::open(filename).expect("Failed to open file for reading"))
-            as Box<dyn Read>,
-    }
+    open_auto(filename)
+}
+
+// Open a file for reading, decompressing it if it is gzip, zstd or lz4 compressed, as determined
+// by the magic bytes at its start, regardless of its name.  Other files are read as is.
+
+pub fn open_auto(f: impl AsRef<Path>) -> Box<dyn Read> {
+    let f = f.as_ref();
+    try_open_auto(f)
+        .unwrap_or_else(|e| panic!("Could not open file \"{}\": {}", f.to_string_lossy(), e))
+}
+
+// Same as open_auto, but return an error rather than panicking.
+
+pub fn try_open_auto(f: impl AsRef<Path>) -> std::io::Result<Box<dyn Read>> {
+    let mut file = File::open(f)?;
+    let mut magic = Vec::<u8>::new();
+    (&mut file).take(4).read_to_end(&mut magic)?;
+
+    // Put the magic bytes back in front of the rest of the file.
+
+    let r = std::io::Cursor::new(magic.clone()).chain(file);
+    Ok(match magic[..] {
+        [0x1f, 0x8b, ..] => Box::new(MultiGzDecoder::new(r)),
+        [0x28, 0xb5, 0x2f, 0xfd] => Box::new(zstd::Decoder::new(r)?),
+        [0x04, 0x22, 0x4d, 0x18] => Box::new(lz4::Decoder::new(r)?),
+        _ => Box::new(r),
+    })
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -143,22 +363,27 @@ pub fn open_maybe_compressed<P: AsRef<Path>>(filename: P) -> Box<dyn Read> {
 // If that exists, load it into lines.  Otherwise try to load x.
 
 pub fn read_maybe_unzipped(f: impl AsRef<Path>, lines: &mut Vec<String>) {
-    let f = f.as_ref();
     lines.clear();
+    for line in lines_maybe_unzipped(f) {
+        lines.push(line.unwrap());
+    }
+}
+
+// lines_maybe_unzipped( f ): same as read_maybe_unzipped, but return an iterator over the
+// lines, which are read as needed, rather than loading all of them.
+
+pub fn lines_maybe_unzipped(
+    f: impl AsRef<Path>,
+) -> Box<dyn Iterator<Item = std::io::Result<String>>> {
+    let f = f.as_ref();
     if path_exists(f) {
         let gz = MultiGzDecoder::new(File::open(f).unwrap());
-        let b = BufReader::new(gz);
-        for line in b.lines() {
-            lines.push(line.unwrap());
-        }
+        Box::new(BufReader::new(gz).lines())
     } else {
         let g = f.with_extension("");
         let g = g.as_path();
         if path_exists(g) {
-            let b = BufReader::new(File::open(g).unwrap());
-            for line in b.lines() {
-                lines.push(line.unwrap());
-            }
+            Box::new(BufReader::new(File::open(g).unwrap()).lines())
         } else {
             panic!(
                 "Could not find {} or {}.",
@@ -169,28 +394,200 @@ pub fn read_maybe_unzipped(f: impl AsRef<Path>, lines: &mut Vec<String>) {
     }
 }
 
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+// COMPUTE CHECKSUMS
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Compute the sha256 or md5 digest of a file, as a lowercase hex string, for example to check
+// the integrity of an input file.  The file is read in chunks, so it need not fit in memory.
+// The digest is of the raw bytes of the file: compressed files (e.g. .gz or .lz4) are not
+// decompressed, so the result agrees with what sha256sum or md5sum would give.
+
+pub fn file_sha256(f: impl AsRef<Path>) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    for_each_chunk(f.as_ref(), |b| hasher.update(b))?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+// The md5 digest is computed using the Digest trait from md-5, rather than that from sha2, since
+// these are different traits if an older version of sha2 is used.
+
+pub fn file_md5(f: impl AsRef<Path>) -> std::io::Result<String> {
+    let mut hasher = <md5::Md5 as md5::Digest>::new();
+    for_each_chunk(f.as_ref(), |b| md5::Digest::update(&mut hasher, b))?;
+    Ok(to_hex(&md5::Digest::finalize(hasher)))
+}
+
+fn for_each_chunk(f: &Path, mut process: impl FnMut(&[u8])) -> std::io::Result<()> {
+    let mut file = File::open(f)?;
+    let mut buf = vec![0_u8; 1 << 16];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        process(&buf[0..n]);
+    }
+}
+
+fn to_hex(x: &[u8]) -> String {
+    let mut hex = String::new();
+    for b in x.iter() {
+        hex += &format!("{:02x}", b);
+    }
+    hex
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // CODE TO DO READS AND WRITES USING SERDE
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
+// ObjError: the error returned by try_write_obj, try_read_obj and their lz4 versions.  This
+// records the file and the step that failed, and keeps the underlying IO or bincode error as
+// its source.
+
+#[derive(Debug)]
+pub struct ObjError {
+    path: PathBuf,
+    step: ObjStep,
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ObjStep {
+    Create,
+    Open,
+    Fail(&'static str),        // the name of the function that failed
+    Deserialize(&'static str), // likewise
+}
+
+impl ObjError {
+    fn new(
+        path: impl AsRef<Path>,
+        step: ObjStep,
+        source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+    ) -> ObjError {
+        ObjError {
+            path: path.as_ref().to_path_buf(),
+            step,
+            source: source.into(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let (p, e) = (&self.path, &self.source);
+        match self.step {
+            ObjStep::Create => write!(f, "couldn't create file {:?}: {}", p, e),
+            ObjStep::Open => write!(f, "couldn't open file {:?}: {}", p, e),
+            ObjStep::Fail(name) => write!(f, "{} of file {:?} failed: {}", name, p, e),
+            ObjStep::Deserialize(name) => {
+                write!(f, "{} of file {:?} failed to deserialize: {}", name, p, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
 pub fn write_obj<T: Serialize, P: AsRef<Path> + Debug>(g: &T, filename: P) {
-    let f = match std::fs::File::create(&filename) {
-        Err(err) => panic!("couldn't create file {:?}: {}", filename, err),
-        Ok(f) => f,
-    };
+    try_write_obj(g, filename).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// Same as write_obj, but return an error instead of panicking.  The error is an ObjError,
+// whose message includes the filename, and whose source is the bincode or IO error.
+
+pub fn try_write_obj<T: Serialize, P: AsRef<Path> + Debug>(
+    g: &T,
+    filename: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let f = std::fs::File::create(&filename)
+        .map_err(|e| ObjError::new(&filename, ObjStep::Create, e))?;
     let mut writer = std::io::BufWriter::new(f);
+    let fail = ObjStep::Fail("write_obj");
+    serialize_into(&mut writer, &g).map_err(|e| ObjError::new(&filename, fail, e))?;
+    writer
+        .flush()
+        .map_err(|e| ObjError::new(&filename, fail, e))?;
+    Ok(())
+}
+
+// Same as write_obj, but using open_for_write_atomic, so that if writing fails, the file is
+// unchanged.
+
+pub fn write_obj_atomic<T: Serialize, P: AsRef<Path> + Debug>(g: &T, filename: P) {
+    let mut writer = open_for_write_atomic(&filename);
     serialize_into(&mut writer, &g)
-        .unwrap_or_else(|_| panic!("write_obj of file {:?} failed", filename))
+        .unwrap_or_else(|_| panic!("write_obj_atomic of file {:?} failed", filename));
+    writer.commit();
 }
 
 pub fn read_obj<T: DeserializeOwned, P: AsRef<Path> + Debug>(filename: P) -> T {
-    let f = match std::fs::File::open(&filename) {
-        Err(err) => panic!("couldn't open file {:?}: {}", filename, err),
-        Ok(f) => f,
-    };
+    try_read_obj(filename).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// Same as read_obj, but return an error instead of panicking, e.g. if the file is not a valid
+// serialization of a T.  The error is an ObjError, whose message includes the filename and says
+// whether opening or deserializing the file failed, and whose source is the bincode or IO
+// error.
+
+pub fn try_read_obj<T: DeserializeOwned, P: AsRef<Path> + Debug>(
+    filename: P,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let f =
+        std::fs::File::open(&filename).map_err(|e| ObjError::new(&filename, ObjStep::Open, e))?;
     let mut reader = std::io::BufReader::new(f);
-    deserialize_from(&mut reader)
-        .unwrap_or_else(|_| panic!("read_obj of file {:?} failed", filename))
+    Ok(deserialize_from(&mut reader)
+        .map_err(|e| ObjError::new(&filename, ObjStep::Deserialize("read_obj"), e))?)
+}
+
+// Same as write_obj and read_obj, but the file is lz4 compressed.
+
+pub fn write_obj_lz4<T: Serialize, P: AsRef<Path> + Debug>(g: &T, filename: P) {
+    try_write_obj_lz4(g, filename).unwrap_or_else(|e| panic!("{}", e))
+}
+
+pub fn read_obj_lz4<T: DeserializeOwned, P: AsRef<Path> + Debug>(filename: P) -> T {
+    try_read_obj_lz4(filename).unwrap_or_else(|e| panic!("{}", e))
+}
+
+// Same as try_write_obj and try_read_obj, but the file is lz4 compressed.
+
+pub fn try_write_obj_lz4<T: Serialize, P: AsRef<Path> + Debug>(
+    g: &T,
+    filename: P,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let f = std::fs::File::create(&filename)
+        .map_err(|e| ObjError::new(&filename, ObjStep::Create, e))?;
+    let fail = ObjStep::Fail("write_obj_lz4");
+    let mut encoder = lz4::EncoderBuilder::new()
+        .build(f)
+        .map_err(|e| ObjError::new(&filename, fail, e))?;
+    serialize_into(&mut encoder, &g).map_err(|e| ObjError::new(&filename, fail, e))?;
+    let (_, result) = encoder.finish();
+    result.map_err(|e| ObjError::new(&filename, fail, e))?;
+    Ok(())
+}
+
+pub fn try_read_obj_lz4<T: DeserializeOwned, P: AsRef<Path> + Debug>(
+    filename: P,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let f =
+        std::fs::File::open(&filename).map_err(|e| ObjError::new(&filename, ObjStep::Open, e))?;
+    let decoder = lz4::Decoder::new(f)
+        .map_err(|e| ObjError::new(&filename, ObjStep::Fail("read_obj_lz4"), e))?;
+    let mut reader = std::io::BufReader::new(decoder);
+    Ok(deserialize_from(&mut reader)
+        .map_err(|e| ObjError::new(&filename, ObjStep::Deserialize("read_obj_lz4"), e))?)
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -224,114 +621,268 @@ macro_rules! eprintme {
 // Get the value of a metric from a json file or similar.  Returns a string.
 // Removes outer quotes if present.  Panics if file not found, and returns empty
 // string if the metric is not found.
+//
+// This finds the first line containing "metric": , and takes the rest of that line, so it
+// does not parse the file, and also works for some files that are not json.  But it does not
+// work for nested objects, or if several entries are on one line, and it can match a string
+// value containing "metric": .  Also, an empty value cannot be distinguished from a missing
+// metric.  For these cases, use get_metric_value_opt or get_metric_value_json.
 
 pub fn get_metric_value(f: impl AsRef<Path>, metric: &str) -> String {
     let buf = open_for_read![&f];
+    let metric_string = format!("\"{}\": ", metric);
+    for line in buf.lines() {
+        if let Some(t) = scan_metric_line(&line.unwrap(), &metric_string) {
+            return t;
+        }
+    }
+    String::default()
+}
+
+// If a line contains "metric": , return the rest of the line, trimmed as for get_metric_value.
+
+fn scan_metric_line(s: &str, metric_string: &str) -> Option<String> {
+    let start = s.find(metric_string)? + metric_string.len();
+    let mut t = s[start..].to_string();
+    if t.ends_with(' ') {
+        t.pop();
+    }
+    if t.ends_with(',') {
+        t.pop();
+    }
+    if t.ends_with(".0") {
+        t.pop();
+        t.pop();
+    }
+    if t.starts_with('\"') && t.ends_with('\"') {
+        t = t[1..t.len() - 1].to_string();
+    }
+    Some(t)
+}
+
+// Get the value of a metric from a json file, or None if it is not found.  Unlike
+// get_metric_value, this parses the file using get_metric_value_json, so the metric may be a
+// dotted path, and the file must be valid json.  A trailing .0 is removed from a number, so
+// 5.0 becomes 5.  Values that are not strings or numbers are returned as json.
+
+pub fn get_metric_value_opt(f: impl AsRef<Path>, metric: &str) -> Option<String> {
+    get_metric_value_json(f, metric).map(|v| metric_value_string(&v))
+}
+
+// Same as get_metric_value, for several metrics, but reading the file only once, in a single
+// pass.  Returns a map from metric to value, for those metrics that are found.  The values are
+// exactly as get_metric_value would return them.
+
+pub fn get_metric_values(f: impl AsRef<Path>, metrics: &[&str]) -> HashMap<String, String> {
+    let buf = open_for_read![&f];
+    let metric_strings = metrics
+        .iter()
+        .map(|m| format!("\"{}\": ", m))
+        .collect::<Vec<_>>();
+    let mut values = HashMap::<String, String>::new();
     for line in buf.lines() {
         let s = line.unwrap();
-        let metric_string = format!("\"{}\": ", metric);
-        if s.contains(&metric_string) {
-            let mut t = s.after(&metric_string).to_string();
-            if t.ends_with(' ') {
-                t.pop();
-            }
-            if t.ends_with(',') {
-                t.pop();
+        for (&metric, metric_string) in metrics.iter().zip(metric_strings.iter()) {
+            if !values.contains_key(metric) {
+                if let Some(t) = scan_metric_line(&s, metric_string) {
+                    values.insert(metric.to_string(), t);
+                }
             }
-            if t.ends_with(".0") {
-                t.pop();
-                t.pop();
-            }
-            if t.starts_with('\"') && t.ends_with('\"') {
-                t = t[1..t.len() - 1].to_string();
+        }
+        if values.len() == metrics.len() {
+            break;
+        }
+    }
+    values
+}
+
+fn metric_value_string(v: &serde_json::Value) -> String {
+    match v {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Number(n) => {
+            let t = n.to_string();
+            match t.strip_suffix(".0") {
+                Some(t) => t.to_string(),
+                None => t,
             }
-            return t.to_string();
         }
+        v => v.to_string(),
     }
-    String::default()
+}
+
+fn find_metric<'a>(v: &'a serde_json::Value, metric: &str) -> Option<&'a serde_json::Value> {
+    if let Some(x) = v.get(metric) {
+        return Some(x);
+    }
+    let mut x = v;
+    for key in metric.split('.') {
+        x = x.get(key)?;
+    }
+    Some(x)
+}
+
+// Get the value of a metric from a json file, or None if it is not present.  The metric may
+// be a top-level key, or a dotted path like "cells.total", to descend into nested objects.  A
+// top-level key containing dots takes precedence.  This parses the file using read_json, so the
+// file may be compressed, and panics if it cannot be read or is not valid json.  Use
+// read_metrics_map to get many metrics from the same file.
+
+pub fn get_metric_value_json(f: impl AsRef<Path>, metric: &str) -> Option<serde_json::Value> {
+    let v = read_json(f).unwrap_or_else(|e| panic!("{}", e));
+    find_metric(&v, metric).cloned()
+}
+
+// Same as get_metric_value_json, but return the value as an f64, or None if the metric is not
+// present or is not a number.
+
+pub fn get_metric_f64(f: impl AsRef<Path>, metric: &str) -> Option<f64> {
+    get_metric_value_json(f, metric).and_then(|v| v.as_f64())
+}
+
+// Read all the metrics from a json file whose top level is an object, returning a map from
+// metric name to value.  This parses the file once, so is much faster than calling
+// get_metric_value repeatedly.  This parses the file using read_json, so the file may be
+// compressed, and panics if it cannot be read or is not valid json, or if the top level is not
+// an object.
+
+pub fn read_metrics_map(f: impl AsRef<Path>) -> HashMap<String, serde_json::Value> {
+    let f = f.as_ref();
+    match read_json(f).unwrap_or_else(|e| panic!("{}", e)) {
+        serde_json::Value::Object(m) => m.into_iter().collect(),
+        _ => panic!(
+            "json file {} does not contain a top-level object",
+            f.to_string_lossy()
+        ),
+    }
+}
+
+// Read and parse a json file.  The file is opened using try_open_auto, so it is decompressed if
+// it is gzip, zstd or lz4 compressed, regardless of its name.  On failure, return an error message that includes the file name, and for a parse error,
+// the line and column at which parsing failed.
+
+pub fn read_json(f: impl AsRef<Path>) -> Result<serde_json::Value, String> {
+    let f = f.as_ref();
+    let reader = try_open_auto(f)
+        .map_err(|e| format!("could not open json file \"{}\": {}", f.display(), e))?;
+    serde_json::from_reader(BufReader::new(reader))
+        .map_err(|e| format!("failed to parse json file \"{}\": {}", f.display(), e))
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // CODE FOR STREAMING A JSON VECTOR
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
-// Read an entry from a json file that represents a vector.  This is not completely
-// general as it depends on assumptions about the formatting of the file.
+// Read an entry from a json file that represents a vector.  Each call returns the next entry,
+// as raw bytes, without parsing it, or None at the end of the vector.  Any layout is allowed,
+// e.g. the whole vector may be on one line, or each entry may be on its own lines.
+//
+// This does not check that the entries are valid json.  To tell the start of the vector from
+// the start of an entry that is itself a vector, the comma after each entry is left to be read
+// by the next call.
 //
 // To compare to and probably replace with:
 // https://martian-lang.github.io/martian-rust/doc/martian_filetypes/json_file/
 // index.html#lazy-readwrite-example
 
 pub fn read_vector_entry_from_json<R: BufRead>(json: &mut R) -> Result<Option<Vec<u8>>, String> {
-    let mut line = String::new();
-    if json.read_line(&mut line).is_err() || line == *"" || line == *"[]" {
-        return Ok(None);
-    }
-    if line == *"[\n" {
-        line.clear();
-        if json.read_line(&mut line).is_err() {
-            return Err(
-                "\nProblem reading json file, probably due to a defect in it.\n".to_string(),
-            );
+    let truncated =
+        || "\nError reading json file.  It is possible that the file was truncated.\n".to_string();
+    let defective = || "\nProblem reading json file, probably due to a defect in it.\n".to_string();
+    let peek = |json: &mut R| -> Result<Option<u8>, String> {
+        match json.fill_buf() {
+            Ok(buf) => Ok(buf.first().copied()),
+            Err(_) => Err("\nSomething appears to be defective in a json file.\n".to_string()),
         }
-    }
-    let mut entry = Vec::<u8>::new();
-    let (mut curlies, mut bracks, mut quotes) = (0_isize, 0_isize, 0_isize);
-    let mut s = line.as_bytes();
-    loop {
-        if (s == b"]" || s == b"]\n") && curlies == 0 && bracks == 0 && quotes % 2 == 0 {
-            if !entry.is_empty() {
-                return Ok(Some(entry));
-            } else {
-                return Ok(None);
+    };
+    let skip_whitespace = |json: &mut R| -> Result<Option<u8>, String> {
+        loop {
+            match peek(json)? {
+                Some(c) if c.is_ascii_whitespace() => json.consume(1),
+                c => return Ok(c),
             }
         }
-        let mut cpos = -1_isize;
-        if s.is_empty() {
-            return Err("\nError reading json file.  It is possible that the file \
-                was truncated.\n"
-                .to_string());
+    };
+
+    // Find the start of the entry, which is after the opening bracket of the vector, or after
+    // the comma following the previous entry.
+
+    match skip_whitespace(json)? {
+        None => return Ok(None),
+        Some(b']') => {
+            json.consume(1);
+            return Ok(None);
         }
-        for i in (0..s.len() - 1).rev() {
-            if s[i] == b',' {
-                cpos = i as isize;
-                break;
-            }
-            if s[i] != b' ' {
-                break;
-            }
+        Some(b'[') | Some(b',') => json.consume(1),
+        Some(_) => return Err(defective()),
+    }
+    match skip_whitespace(json)? {
+        None => return Err(truncated()),
+        Some(b']') => {
+            json.consume(1);
+            return Ok(None);
         }
-        let mut escaped = false;
-        for i in 0..s.len() {
-            if !escaped && s[i] == b'"' {
-                quotes += 1;
-            } else if !escaped && quotes % 2 == 0 {
-                match s[i] {
-                    b'{' => curlies += 1,
-                    b'}' => curlies -= 1,
-                    b'[' => bracks += 1,
-                    b']' => bracks -= 1,
-                    b',' => {
-                        if i as isize == cpos && curlies == 0 && bracks == 0 && quotes % 2 == 0 {
-                            return Ok(Some(entry));
-                        }
-                    }
-                    _ => {}
-                };
-            }
-            if s[i] == b'\\' && !escaped {
-                escaped = true;
-            } else {
+        Some(b',') => return Err(defective()),
+        Some(_) => {}
+    }
+
+    // Read the entry, up to the next comma or closing bracket that is not nested in it.
+
+    let mut entry = Vec::<u8>::new();
+    let (mut depth, mut quoted, mut escaped) = (0_isize, false, false);
+    loop {
+        let c = peek(json)?.ok_or_else(truncated)?;
+        if !quoted && depth == 0 && (c == b',' || c == b']') {
+            break;
+        }
+        json.consume(1);
+        if quoted {
+            if escaped {
                 escaped = false;
+            } else if c == b'\\' {
+                escaped = true;
+            } else if c == b'"' {
+                quoted = false;
+            }
+        } else {
+            match c {
+                b'"' => quoted = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth -= 1,
+                _ => {}
             }
-            entry.push(s[i]);
-        }
-        line.clear();
-        if json.read_line(&mut line).is_err() {
-            return Err("\nSomething appears to be defective in a json file.\n".to_string());
         }
-        s = line.as_bytes();
+        entry.push(c);
     }
+    while matches!(entry.last(), Some(c) if c.is_ascii_whitespace()) {
+        entry.pop();
+    }
+    Ok(Some(entry))
+}
+
+// Iterate over the entries of a json file that represents a vector, parsing each entry as a T,
+// using read_vector_entry_from_json.  Iteration stops after the first error, e.g. if the file
+// was truncated.
+
+pub fn json_array_stream<T: DeserializeOwned, R: BufRead>(
+    mut json: R,
+) -> impl Iterator<Item = Result<T, String>> {
+    let mut done = false;
+    std::iter::from_fn(move || {
+        if done {
+            return None;
+        }
+        let x = match read_vector_entry_from_json(&mut json) {
+            Ok(Some(entry)) => serde_json::from_slice::<T>(&entry)
+                .map_err(|e| format!("\nFailed to parse json entry: {}.\n", e)),
+            Ok(None) => {
+                done = true;
+                return None;
+            }
+            Err(e) => Err(e),
+        };
+        done = x.is_err();
+        Some(x)
+    })
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -346,3 +897,455 @@ pub fn read_to_string_safe<P: AsRef<Path>>(path: P) -> String {
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p io_utils
+
+    use crate::{
+        file_md5, file_sha256, get_metric_f64, get_metric_value, get_metric_value_json,
+        get_metric_value_opt, get_metric_values, json_array_stream, lines_maybe_unzipped,
+        mkdir_p_result, mkdir_p_safe, open_auto, open_for_write_atomic, open_maybe_compressed,
+        path_exists, read_json, read_maybe_unzipped, read_metrics_map, read_obj, read_obj_lz4,
+        read_to_string_safe, read_vector_entry_from_json, try_read_obj, try_read_obj_lz4,
+        try_write_obj, write_lines_gz, write_obj, write_obj_atomic, write_obj_lz4, ObjError,
+    };
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    #[test]
+    fn test_read_metrics_map() {
+        let f = std::env::temp_dir().join(format!("io_utils_metrics_{}.json", std::process::id()));
+        fs::write(
+            &f,
+            "{\n    \"barcodes\": 1000,\n    \"fraction\": 0.5,\n    \"name\": \"woof\"\n}\n",
+        )
+        .unwrap();
+        let m = read_metrics_map(&f);
+        let mut z = flate2::write::GzEncoder::new(File::create(&f).unwrap(), Default::default());
+        z.write_all(b"{\"barcodes\": 1000}").unwrap();
+        z.finish().unwrap();
+        assert_eq!(read_metrics_map(&f)["barcodes"].as_i64(), Some(1000));
+        fs::remove_file(&f).unwrap();
+        assert_eq!(m.len(), 3);
+        assert_eq!(m["barcodes"].as_i64(), Some(1000));
+        assert_eq!(m["fraction"].as_f64(), Some(0.5));
+        assert_eq!(m["name"].as_str(), Some("woof"));
+        assert!(!m.contains_key("missing"));
+    }
+
+    #[test]
+    fn test_get_metric_value_json() {
+        let f = std::env::temp_dir().join(format!("io_utils_metric_{}.json", std::process::id()));
+        fs::write(
+            &f,
+            "{\n    \"note\": \"\\\"cells\\\": 7\",\n    \"median_cells\": 3,\n    \
+             \"nested\": {\"cells\": 5},\n    \"cells\": 1.0e6,\n    \"name\": \"woof\"\n}\n",
+        )
+        .unwrap();
+        let cells = get_metric_f64(&f, "cells");
+        let name = get_metric_value_json(&f, "name");
+        let missing = get_metric_value_json(&f, "dogs");
+        let not_a_number = get_metric_f64(&f, "name");
+        fs::remove_file(&f).unwrap();
+        assert_eq!(cells, Some(1.0e6));
+        assert_eq!(name, Some(serde_json::Value::from("woof")));
+        assert_eq!(missing, None);
+        assert_eq!(not_a_number, None);
+    }
+
+    #[test]
+    fn test_get_metric_value_opt() {
+        let f = std::env::temp_dir().join(format!("io_utils_nested_{}.json", std::process::id()));
+        fs::write(
+            &f,
+            "{\n  \"cells\": {\n    \"total\": 1000.0,\n    \"named\": {\"x\": \"\"}\n  },\n  \
+             \"a.b\": 7,\n  \"a\": {\"b\": 8},\n  \"list\": [1, 2]\n}\n",
+        )
+        .unwrap();
+        let total = get_metric_value_opt(&f, "cells.total");
+        let empty = get_metric_value_opt(&f, "cells.named.x");
+        let missing = get_metric_value_opt(&f, "cells.named.y");
+        let dotted = get_metric_value_opt(&f, "a.b");
+        let list = get_metric_value_opt(&f, "list");
+        let too_deep = get_metric_value_opt(&f, "cells.total.x");
+        let legacy_list = get_metric_value(&f, "list");
+        let legacy_nested = get_metric_value(&f, "total");
+        fs::remove_file(&f).unwrap();
+        assert_eq!(total, Some("1000".to_string()));
+        assert_eq!(empty, Some(String::new()));
+        assert_eq!(missing, None);
+        assert_eq!(dotted, Some("7".to_string()));
+        assert_eq!(list, Some("[1,2]".to_string()));
+        assert_eq!(too_deep, None);
+        assert_eq!(legacy_list, "[1, 2]");
+        assert_eq!(legacy_nested, "1000");
+    }
+
+    #[test]
+    fn test_get_metric_values() {
+        let f = std::env::temp_dir().join(format!("io_utils_values_{}.json", std::process::id()));
+        fs::write(
+            &f,
+            "{\n    \"a\": 1.0,\n    \"b\": \"woof\",\n    \"c\": {\"d\": 2.5},\n    \
+             \"e\": null,\n    \"big\": 1.0e6,\n    \"half\": 0.50\n}\n",
+        )
+        .unwrap();
+        let metrics = ["a", "b", "d", "e", "big", "half", "missing"];
+        let values = get_metric_values(&f, &metrics);
+        assert_eq!(values.len(), 6);
+        for m in metrics {
+            assert_eq!(
+                values.get(m).cloned().unwrap_or_default(),
+                get_metric_value(&f, m)
+            );
+        }
+        fs::remove_file(&f).unwrap();
+        assert_eq!(values["a"], "1");
+        assert_eq!(values["e"], "null");
+        assert_eq!(values["big"], "1.0e6");
+        assert_eq!(values["half"], "0.50");
+    }
+
+    #[test]
+    fn test_file_checksums() {
+        let f = std::env::temp_dir().join(format!("io_utils_checksum_{}.txt", std::process::id()));
+        fs::write(&f, "abc").unwrap();
+        let sha = file_sha256(&f).unwrap();
+        let md5 = file_md5(&f).unwrap();
+        fs::remove_file(&f).unwrap();
+        assert_eq!(
+            sha,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert!(file_sha256(&f).is_err());
+    }
+
+    #[test]
+    fn test_mkdir_p() {
+        let root = std::env::temp_dir().join(format!("io_utils_mkdir_{}", std::process::id()));
+        let d = root.join("a/b/c");
+        mkdir_p_safe(&d);
+        assert!(d.is_dir());
+        mkdir_p_safe(&d);
+        let f = root.join("a/file");
+        fs::write(&f, "woof").unwrap();
+        let e = mkdir_p_result(f.join("d")).unwrap_err();
+        assert!(e.contains(&*f.to_string_lossy()));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_read_json() {
+        let dir = std::env::temp_dir();
+        let f = dir.join(format!("io_utils_read_json_{}.json", std::process::id()));
+        fs::write(&f, "{\n  \"a\": [1, 2],\n  \"b\": [3 4]\n}\n").unwrap();
+        let e = read_json(&f).unwrap_err();
+        fs::remove_file(&f).unwrap();
+        assert!(e.contains(&*f.to_string_lossy()));
+        assert!(e.contains("line 3 column"), "{}", e);
+
+        // Check gzipped files, including one whose name does not say so.
+
+        for ext in ["json.gz", "json"] {
+            let g = dir.join(format!(
+                "io_utils_read_json_{}_z.{}",
+                std::process::id(),
+                ext
+            ));
+            let mut z =
+                flate2::write::GzEncoder::new(File::create(&g).unwrap(), Default::default());
+            z.write_all(b"{\"a\": [1, 2]}").unwrap();
+            z.finish().unwrap();
+            let v = read_json(&g).unwrap();
+            fs::remove_file(&g).unwrap();
+            assert_eq!(v["a"][1], 2);
+            assert!(read_json(&g).is_err());
+        }
+    }
+
+    #[test]
+    fn test_open_maybe_compressed() {
+        let text = b"woof\nmeow\n";
+        let f = |ext: &str| {
+            std::env::temp_dir().join(format!(
+                "io_utils_compressed_{}.{}",
+                std::process::id(),
+                ext
+            ))
+        };
+        fs::write(f("txt"), text).unwrap();
+        let mut z = lz4::EncoderBuilder::new()
+            .build(File::create(f("lz4")).unwrap())
+            .unwrap();
+        z.write_all(text).unwrap();
+        z.finish().1.unwrap();
+        let mut z =
+            flate2::write::GzEncoder::new(File::create(f("gz")).unwrap(), Default::default());
+        z.write_all(text).unwrap();
+        z.finish().unwrap();
+        fs::write(f("zst"), zstd::encode_all(&text[..], 0).unwrap()).unwrap();
+
+        // The extension doesn't matter.
+
+        fs::copy(f("gz"), f("gzip_not_gz")).unwrap();
+        fs::copy(f("lz4"), f("lz4_not_lz4")).unwrap();
+        for ext in ["txt", "lz4", "gz", "zst", "gzip_not_gz", "lz4_not_lz4"] {
+            let mut x = Vec::<u8>::new();
+            open_maybe_compressed(f(ext)).read_to_end(&mut x).unwrap();
+            fs::remove_file(f(ext)).unwrap();
+            assert_eq!(x, text, "failed for {}", ext);
+        }
+    }
+
+    #[test]
+    fn test_open_auto() {
+        let text = b"woof\nmeow\n";
+        let f = |i: usize| {
+            std::env::temp_dir().join(format!("io_utils_auto_{}_{}.txt", std::process::id(), i))
+        };
+        let mut z = lz4::EncoderBuilder::new().build(Vec::new()).unwrap();
+        z.write_all(text).unwrap();
+        let lz4 = z.finish().0;
+        let mut z = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        z.write_all(text).unwrap();
+        let gz = z.finish().unwrap();
+        let zst = zstd::encode_all(&text[..], 0).unwrap();
+        for (i, data) in [&text[..], &b"wo"[..], &lz4, &gz, &zst].iter().enumerate() {
+            fs::write(f(i), data).unwrap();
+            let mut x = Vec::<u8>::new();
+            open_auto(f(i)).read_to_end(&mut x).unwrap();
+            fs::remove_file(f(i)).unwrap();
+            let expected = if i == 1 { &b"wo"[..] } else { &text[..] };
+            assert_eq!(x, expected, "failed for file {}", i);
+        }
+    }
+
+    #[test]
+    fn test_read_vector_entry_from_json() {
+        fn entries(json: &str) -> Result<Vec<String>, String> {
+            let mut r = json.as_bytes();
+            let mut x = Vec::<String>::new();
+            while let Some(entry) = read_vector_entry_from_json(&mut r)? {
+                x.push(String::from_utf8(entry).unwrap());
+            }
+            assert_eq!(read_vector_entry_from_json(&mut r), Ok(None));
+            Ok(x)
+        }
+        let expected = ["{\"a\":1}", "[2,[3]]", "\"x,]\\\"\"", "4"];
+
+        // test 1: compact
+        assert_eq!(
+            entries("[{\"a\":1},[2,[3]],\"x,]\\\"\",4]").unwrap(),
+            expected
+        );
+        // test 2: pretty, with whitespace around the brackets
+        assert_eq!(
+            entries(" [\n  {\"a\":1},\n  [2,[3]] ,\n  \"x,]\\\"\",\n  4\n ]  \n").unwrap(),
+            expected
+        );
+        // test 3: an entry that spans several lines
+        assert_eq!(
+            entries("[\n    {\n        \"a\": [\n            1\n        ]\n    }\n]\n").unwrap(),
+            ["{\n        \"a\": [\n            1\n        ]\n    }"]
+        );
+        // test 4: empty
+        assert_eq!(entries("").unwrap(), Vec::<String>::new());
+        assert_eq!(entries("[]").unwrap(), Vec::<String>::new());
+        assert_eq!(entries("[ \n ]\n").unwrap(), Vec::<String>::new());
+        // test 5: truncated or defective
+        assert!(entries("[{\"a\":1},{\"a\"")
+            .unwrap_err()
+            .contains("truncated"));
+        assert!(entries("[{\"a\":1},").unwrap_err().contains("truncated"));
+        assert!(entries("[{\"a\":1},,]").unwrap_err().contains("defect"));
+        assert!(entries("{\"a\":1}").unwrap_err().contains("defect"));
+    }
+
+    #[test]
+    fn test_json_array_stream() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Pet {
+            name: String,
+            legs: u32,
+        }
+        let json = "[\n    {\n        \"name\": \"dog, cat\",\n        \"legs\": 4\n    },\n    \
+                    {\"name\": \"bird\", \"legs\": 2},\n    {\"name\": \"snake\"}\n]\n";
+        let x: Vec<Result<Pet, String>> = json_array_stream(json.as_bytes()).collect();
+        assert_eq!(x.len(), 3);
+        assert_eq!(
+            x[0],
+            Ok(Pet {
+                name: "dog, cat".to_string(),
+                legs: 4
+            })
+        );
+        assert_eq!(x[1].as_ref().unwrap().name, "bird");
+        assert!(x[2].as_ref().unwrap_err().contains("missing field `legs`"));
+
+        // A truncated file yields an error, and then nothing.
+
+        let truncated = &json[..json.find("{\"name\": \"snake").unwrap()];
+        let x: Vec<Result<Pet, String>> = json_array_stream(truncated.as_bytes()).collect();
+        assert_eq!(x.len(), 3);
+        assert!(x[2].as_ref().unwrap_err().contains("truncated"));
+        assert_eq!(json_array_stream::<Pet, _>("[]".as_bytes()).count(), 0);
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir();
+        let f = dir.join(format!("io_utils_atomic_{}.bin", std::process::id()));
+        let x = vec![(1_u32, "woof".to_string()), (2, "meow".to_string())];
+        write_obj_atomic(&x, &f);
+        let y: Vec<(u32, String)> = read_obj(&f);
+        assert_eq!(x, y);
+
+        // If the writer is dropped before commit, the file is unchanged, and the temporary file
+        // is removed.
+
+        {
+            let mut out = open_for_write_atomic(&f);
+            fwriteln!(out, "{}", "garbage");
+            assert!(path_exists(&out.tmp));
+        }
+        let y: Vec<(u32, String)> = read_obj(&f);
+        assert_eq!(x, y);
+        let mut out = open_for_write_atomic(&f);
+        let tmp = out.tmp.clone();
+        fwrite!(out, "{}", "bark");
+        out.commit();
+        assert!(!path_exists(tmp));
+        assert_eq!(read_to_string_safe(&f), "bark");
+
+        // Concurrent writers never leave a partial file.
+
+        let f = &f;
+        write_obj_atomic(&vec![0_u32; 10_000], f);
+        std::thread::scope(|s| {
+            for i in 1..=4_u32 {
+                s.spawn(move || {
+                    for _ in 0..10 {
+                        write_obj_atomic(&vec![i; 10_000], f);
+                        let y: Vec<u32> = read_obj(f);
+                        assert_eq!(y.len(), 10_000);
+                        assert!(y.iter().all(|&v| v == y[0]));
+                    }
+                });
+            }
+        });
+        let y: Vec<u32> = read_obj(f);
+        assert!(y[0] >= 1 && y == vec![y[0]; 10_000]);
+        fs::remove_file(f).unwrap();
+    }
+
+    #[test]
+    fn test_try_read_obj() {
+        let dir = std::env::temp_dir();
+        let f = dir.join(format!("io_utils_try_read_obj_{}.bin", std::process::id()));
+        let x = vec![1_u64, 2, 3];
+        try_write_obj(&x, &f).unwrap();
+        assert_eq!(try_read_obj::<Vec<u64>, _>(&f).unwrap(), x);
+
+        // A truncated file gives an error, as does a missing one.
+
+        fs::write(&f, &fs::read(&f).unwrap()[0..12]).unwrap();
+        let e = try_read_obj::<Vec<u64>, _>(&f).unwrap_err().to_string();
+        assert!(e.starts_with("read_obj of file"), "{}", e);
+        assert!(e.contains(&*f.to_string_lossy()));
+
+        // So does a corrupt one, here having a string that is not valid UTF-8.
+
+        let mut corrupt = vec![1_u8, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0];
+        corrupt.extend([0xff, 0xfe]);
+        fs::write(&f, &corrupt).unwrap();
+        let e = try_read_obj::<Vec<String>, _>(&f).unwrap_err();
+        assert!(e.to_string().contains("failed to deserialize"), "{}", e);
+        let e = e.downcast_ref::<ObjError>().unwrap();
+        assert_eq!(e.path(), f);
+        let source = std::error::Error::source(e).unwrap();
+        assert!(source.downcast_ref::<bincode::Error>().is_some());
+        fs::remove_file(&f).unwrap();
+
+        // The IO error can be recovered from a missing file.
+
+        let e = try_read_obj::<Vec<u64>, _>(&f).unwrap_err();
+        assert!(e.to_string().starts_with("couldn't open file"), "{}", e);
+        let source = e.downcast_ref::<ObjError>().unwrap().source.as_ref();
+        let source = source.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(source.kind(), std::io::ErrorKind::NotFound);
+        assert!(try_write_obj(&x, dir.join("no/such/dir/file.bin")).is_err());
+    }
+
+    #[test]
+    fn test_write_obj_lz4() {
+        let dir = std::env::temp_dir();
+        let f = dir.join(format!("io_utils_obj_{}.bin", std::process::id()));
+        let g = dir.join(format!("io_utils_obj_{}.bin.lz4", std::process::id()));
+        let x = (0..100_000).map(|i| i % 10).collect::<Vec<u32>>();
+        write_obj(&x, &f);
+        write_obj_lz4(&x, &g);
+        let y: Vec<u32> = read_obj_lz4(&g);
+        assert_eq!(x, y);
+        let (nf, ng) = (
+            fs::metadata(&f).unwrap().len(),
+            fs::metadata(&g).unwrap().len(),
+        );
+
+        // A file that is not lz4 compressed gives an error, as does a missing one.
+
+        let e = try_read_obj_lz4::<Vec<u32>, _>(&f).unwrap_err().to_string();
+        assert!(e.starts_with("read_obj_lz4 of file"), "{}", e);
+        assert!(e.contains(&*f.to_string_lossy()));
+        fs::remove_file(&f).unwrap();
+        fs::remove_file(&g).unwrap();
+        let e = try_read_obj_lz4::<Vec<u32>, _>(&g).unwrap_err().to_string();
+        assert!(e.starts_with("couldn't open file"), "{}", e);
+        assert!(ng * 20 < nf, "{} versus {}", ng, nf);
+    }
+
+    #[test]
+    fn test_lines_maybe_unzipped() {
+        let dir = std::env::temp_dir();
+        let f = dir.join(format!("io_utils_lines_{}.txt.gz", std::process::id()));
+        let lines = (0..10_000)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>();
+        write_lines_gz(&f, &lines);
+        let mut n = 0;
+        for (i, line) in lines_maybe_unzipped(&f).enumerate() {
+            assert_eq!(line.unwrap(), lines[i]);
+            n += 1;
+        }
+        assert_eq!(n, lines.len());
+        fs::remove_file(&f).unwrap();
+
+        // If the .gz file does not exist, the uncompressed file is used.
+
+        fs::write(f.with_extension(""), "woof\nmeow\n").unwrap();
+        let x = lines_maybe_unzipped(&f).collect::<std::io::Result<Vec<_>>>();
+        fs::remove_file(f.with_extension("")).unwrap();
+        assert_eq!(x.unwrap(), ["woof", "meow"]);
+    }
+
+    #[test]
+    fn test_write_gz() {
+        let dir = std::env::temp_dir();
+        let f = dir.join(format!("io_utils_write_gz_{}.txt.gz", std::process::id()));
+        let lines = vec!["woof".to_string(), String::new(), "meow meow".to_string()];
+        write_lines_gz(&f, &lines);
+        let mut x = Vec::<String>::new();
+        read_maybe_unzipped(&f, &mut x);
+        assert_eq!(x, lines);
+        {
+            let mut out = open_for_write_gz_new![&f];
+            fwriteln!(out, "{}", "bark");
+        }
+        read_maybe_unzipped(&f, &mut x);
+        fs::remove_file(&f).unwrap();
+        assert_eq!(x, ["bark"]);
+    }
+}
@@ -8,7 +8,7 @@ use debruijn::{
     Kmer, Mer, Vmer,
 };
 use rayon::prelude::*;
-use std::iter::Extend;
+use std::{cmp::min, collections::VecDeque, iter::Extend};
 use vector_utils::{
     lower_bound1_3, resize_without_setting, unique_sort, upper_bound, upper_bound1_3,
 };
@@ -183,6 +183,60 @@ pub fn make_kmer_lookup_oriented_single(dv: &[DnaString], x: &mut Vec<(Kmer20, i
     // Note that the parallel version of this sort x.part_sort() is faster in C++.
 }
 
+// Create a minimizer index.  As in make_kmer_lookup_20_oriented_single, each kmer is replaced
+// by the min of it and its rc, and if we use rc, the position is encoded as -pos-1.  Then for
+// each window of consecutive kmers on a DnaString, only the smallest of these canonical kmers is
+// kept (the leftmost one, in case of a tie).  A sequence having fewer than window kmers (but at
+// least one) is treated as a single window.  The result is sorted.
+//
+// Since adjacent windows usually share their minimizer, the index has roughly 2/(window+1) as
+// many entries as the full kmer lookup.  The tradeoff is sensitivity: a query is guaranteed to
+// share a kmer with the index only if it matches at least window+k-1 consecutive bases of a
+// DnaString, so larger windows give a smaller index but miss shorter matches.
+
+pub fn make_minimizer_lookup<K: Kmer>(dv: &[DnaString], window: usize) -> Vec<(K, i32, i32)> {
+    assert!(window >= 1);
+    let mut x = Vec::<(K, i32, i32)>::new();
+    for (i, b) in dv.iter().enumerate() {
+        if b.len() < K::k() {
+            continue;
+        }
+        let nkmers = b.len() - K::k() + 1;
+        let w = min(window, nkmers);
+
+        // Traverse the kmers, keeping track of the positions of the kmers that could still be
+        // the minimum of a window, from left to right, in increasing order of kmer.
+
+        let mut canon = Vec::<(K, i32)>::with_capacity(nkmers);
+        let mut cands = VecDeque::<usize>::new();
+        let mut last = None;
+        for (j, kmer) in b.iter_kmers::<K>().enumerate() {
+            let kmer_rc = kmer.rc();
+            if kmer < kmer_rc {
+                canon.push((kmer, j as i32));
+            } else {
+                canon.push((kmer_rc, -(j as i32) - 1));
+            }
+            while !cands.is_empty() && canon[*cands.back().unwrap()].0 > canon[j].0 {
+                cands.pop_back();
+            }
+            cands.push_back(j);
+            if j + 1 >= w {
+                while *cands.front().unwrap() + w <= j {
+                    cands.pop_front();
+                }
+                let m = *cands.front().unwrap();
+                if last != Some(m) {
+                    x.push((canon[m].0, i as i32, canon[m].1));
+                    last = Some(m);
+                }
+            }
+        }
+    }
+    x.sort();
+    x
+}
+
 // Determine if a sequence perfectly matches in forward orientation.
 
 pub fn match_12(b: &DnaString, dv: &[DnaString], x: &[(Kmer12, i32, i32)]) -> bool {
@@ -206,3 +260,58 @@ pub fn match_12(b: &DnaString, dv: &[DnaString], x: &[(Kmer12, i32, i32)]) -> bo
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p kmer_lookup
+
+    use crate::make_minimizer_lookup;
+    use debruijn::{dna_string::DnaString, kmer::Kmer12, Mer, Vmer};
+    use std::cmp::min;
+    use vector_utils::unique_sort;
+
+    #[test]
+    fn test_make_minimizer_lookup() {
+        let mut dv = Vec::<DnaString>::new();
+        let mut r = 1_u64;
+        for n in [5, 12, 20, 100, 1000].iter() {
+            let mut s = String::new();
+            for _ in 0..*n {
+                r = r
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                s.push(b"ACGT"[(r >> 62) as usize] as char);
+            }
+            dv.push(DnaString::from_dna_string(&s));
+        }
+        let window = 10;
+        let x = make_minimizer_lookup::<Kmer12>(&dv, window);
+        assert!(x.windows(2).all(|w| w[0] <= w[1]));
+
+        // Check that the index consists exactly of the window minima.
+
+        let mut y = Vec::<(Kmer12, i32, i32)>::new();
+        for (i, b) in dv.iter().enumerate() {
+            let mut canon = Vec::<(Kmer12, i32)>::new();
+            for (j, kmer) in b.iter_kmers::<Kmer12>().enumerate() {
+                if kmer < kmer.rc() {
+                    canon.push((kmer, j as i32));
+                } else {
+                    canon.push((kmer.rc(), -(j as i32) - 1));
+                }
+            }
+            if canon.is_empty() {
+                continue;
+            }
+            for win in canon.windows(min(window, canon.len())) {
+                let m = win.iter().min_by_key(|c| c.0).unwrap();
+                y.push((m.0, i as i32, m.1));
+            }
+        }
+        unique_sort(&mut y);
+        assert_eq!(x, y);
+        assert!(x.len() < dv.iter().map(DnaString::len).sum::<usize>() / 3);
+    }
+}
@@ -34,7 +34,9 @@
 //         - value (u16)
 //    (c') data for (c), m4 entries of form
 //         - column identifier (u16)
-//         - value (u32).
+//         - value (u32)
+// 10. the string labels for rows and then columns
+// 11. CRC-32 checksum of all the preceding bytes (u32)                *** ADDED ***
 //
 // The case where the number of columns is > 2^16 is the same except that all the u16 entries are
 // changed to u32.
@@ -43,24 +45,51 @@
 // * read from disk
 // * write to disk
 // * build from (Vec<Vec<(i32,i32)>, Vec<String>, Vec<String>) representation
+// * build from (row, column, value) triplets
 // * report the number of rows and the number of columns
 // * report the sum of entries for a given row
 // * report the sum of entries for a given column
 // * return the value of a given matrix entry
-// * return a given row
+// * return a given row or column
+// * iterate over all rows, or all entries
 // * return a row or column label.
 //
 // The initial version was 0.  In version 1, string labels for rows and columns were added.
 // A version 0 file can no longer be read, except that, you can read the version and exit.
 // All functions after item 4 above will assert strangely or return garbage.  It would be
-// better to first call get_code_version_from_file.
+// better to first call get_code_version_from_file.  In version 2, the checksum was added, and
+// it is verified when a file is read.  Version 1 files can still be read, without verification.
+//
+// A file can either be read into memory, using read_from_file, or mapped into memory, using
+// MirrorSparseMatrix::open_mmap.  The latter avoids reading the entire file up front.
 
 use binary_vec_io::{binary_read_to_ref, binary_read_vec, binary_write_vec};
-use std::cmp::max;
+use memmap2::Mmap;
+use std::{cmp::max, fmt, io, ops::Deref, sync::Arc};
 
 #[derive(Clone)]
 pub struct MirrorSparseMatrix {
-    x: Vec<u8>,
+    x: Bytes,
+}
+
+// The bytes of a MirrorSparseMatrix, which are either owned or mapped from a file.  A file starts
+// with the number of bytes that follow (u64), so these eight bytes are skipped in the mapped case.
+
+#[derive(Clone)]
+enum Bytes {
+    Owned(Vec<u8>),
+    Mapped(Arc<Mmap>),
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Owned(v) => v,
+            Bytes::Mapped(m) => &m[8..],
+        }
+    }
 }
 
 pub fn get_code_version_from_file(f: &str) -> u32 {
@@ -71,28 +100,133 @@ pub fn get_code_version_from_file(f: &str) -> u32 {
     x[10]
 }
 
-pub fn read_from_file(s: &mut MirrorSparseMatrix, f: &str) {
-    let mut ff = std::fs::File::open(f).unwrap();
-    binary_read_vec::<u8>(&mut ff, &mut s.x).unwrap();
-    if s.code_version() != 0 && s.code_version() != 1 {
-        panic!(
-            "\nMirrorSparseMatrix: code_version has to be 0 or 1, but it is {}.\n",
-            s.code_version()
-        );
+// MirrorError: the ways in which reading or writing a MirrorSparseMatrix file can fail.
+
+#[derive(Debug)]
+pub enum MirrorError {
+    Io(io::Error),
+    BadCodeVersion(u32),
+    BadStorageVersion(u32),
+    BadChecksum,
+    Truncated,
+    Uninitialized,
+    UnsupportedUsize,
+}
+
+impl fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MirrorError::Io(e) => write!(f, "{}", e),
+            MirrorError::BadCodeVersion(v) => {
+                write!(f, "code_version has to be 0, 1 or 2, but it is {}", v)
+            }
+            MirrorError::BadStorageVersion(v) => {
+                write!(f, "storage_version has to be 0 or 1, but it is {}", v)
+            }
+            MirrorError::BadChecksum => {
+                write!(
+                    f,
+                    "the checksum is wrong, so the file is corrupt or truncated"
+                )
+            }
+            MirrorError::Truncated => write!(f, "the file is truncated"),
+            MirrorError::Uninitialized => write!(f, "the matrix is not initialized"),
+            MirrorError::UnsupportedUsize => {
+                write!(f, "memory mapping requires a 64-bit usize")
+            }
+        }
     }
-    if s.storage_version() != 0 && s.storage_version() != 1 {
-        panic!(
-            "\nMirrorSparseMatrix: storage_version has to be 0 or 1, but it is {}.\n",
-            s.storage_version()
-        );
+}
+
+impl std::error::Error for MirrorError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MirrorError::Io(e) => Some(e),
+            _ => None,
+        }
     }
 }
 
+impl From<io::Error> for MirrorError {
+    fn from(e: io::Error) -> Self {
+        MirrorError::Io(e)
+    }
+}
+
+pub fn read_from_file(s: &mut MirrorSparseMatrix, f: &str) {
+    try_read_from_file(s, f)
+        .unwrap_or_else(|e| panic!("\nMirrorSparseMatrix: failed to read {}: {}.\n", f, e));
+}
+
 pub fn write_to_file(s: &MirrorSparseMatrix, f: &str) {
-    assert!(s.code_version() > 0);
-    let mut ff =
-        std::fs::File::create(f).unwrap_or_else(|_| panic!("Failed to create file {}.", f));
-    binary_write_vec::<u8>(&mut ff, &s.x).unwrap();
+    try_write_to_file(s, f)
+        .unwrap_or_else(|e| panic!("\nMirrorSparseMatrix: failed to write {}: {}.\n", f, e));
+}
+
+// Same as read_from_file, but return an error rather than panicking.
+
+pub fn try_read_from_file(s: &mut MirrorSparseMatrix, f: &str) -> Result<(), MirrorError> {
+    let mut ff = std::fs::File::open(f)?;
+    let mut x = Vec::<u8>::new();
+    binary_read_vec::<u8>(&mut ff, &mut x)?;
+    if x.len() < MirrorSparseMatrix::header_size() {
+        return Err(MirrorError::Truncated);
+    }
+    let m = MirrorSparseMatrix { x: Bytes::Owned(x) };
+    m.check_versions()?;
+    if m.code_version() >= 2 && !m.checksum_ok() {
+        return Err(MirrorError::BadChecksum);
+    }
+    *s = m;
+    Ok(())
+}
+
+// Same as write_to_file, but return an error rather than panicking.
+
+pub fn try_write_to_file(s: &MirrorSparseMatrix, f: &str) -> Result<(), MirrorError> {
+    if !s.initialized() {
+        return Err(MirrorError::Uninitialized);
+    }
+    if s.code_version() == 0 {
+        return Err(MirrorError::BadCodeVersion(0));
+    }
+    let mut ff = std::fs::File::create(f)?;
+    binary_write_vec::<u8>(&mut ff, &s.x)?;
+    Ok(())
+}
+
+// The table used by crc32, computed at compile time.
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 == 1 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+// Compute the CRC-32 checksum of a byte vector, as used by gzip and zip.
+
+fn crc32(v: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &b in v.iter() {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
 }
 
 fn get_u8_at_pos(v: &[u8], pos: usize) -> u8 {
@@ -143,10 +277,149 @@ fn push_u32(v: &mut Vec<u8>, val: u32) {
     }
 }
 
+// Append the encoding of a row to a byte vector.  The entries are stored in increasing order
+// by column, so if they are not given in that order, they are sorted first.
+
+fn push_row_entries(v: &mut Vec<u8>, x: &[(i32, i32)], storage_version: u32) {
+    let sorted;
+    let x = if x.windows(2).all(|e| e[0].0 <= e[1].0) {
+        x
+    } else {
+        sorted = {
+            let mut y = x.to_vec();
+            y.sort_by_key(|e| e.0);
+            y
+        };
+        &sorted
+    };
+    let push_col = |v: &mut Vec<u8>, col: i32| {
+        if storage_version == 0 {
+            push_u16(v, col as u16);
+        } else {
+            push_u32(v, col as u32);
+        }
+    };
+    let (mut m1, mut m2, mut m4) = (0, 0, 0);
+    for &(_, val) in x.iter() {
+        if val < 256 {
+            m1 += 1;
+        } else if val < 65536 {
+            m2 += 1;
+        } else {
+            m4 += 1;
+        }
+    }
+    if storage_version == 0 {
+        push_u16(v, m1 as u16);
+        push_u16(v, m2 as u16);
+        push_u16(v, m4 as u16);
+    } else {
+        push_u32(v, m1 as u32);
+        push_u32(v, m2 as u32);
+        push_u32(v, m4 as u32);
+    }
+    for &(col, val) in x.iter() {
+        if val < 256 {
+            push_col(v, col);
+            push_u8(v, val as u8);
+        }
+    }
+    for &(col, val) in x.iter() {
+        if (256..65536).contains(&val) {
+            push_col(v, col);
+            push_u16(v, val as u16);
+        }
+    }
+    for &(col, val) in x.iter() {
+        if val >= 65536 {
+            push_col(v, col);
+            push_u32(v, val as u32);
+        }
+    }
+}
+
+// Decode the row that starts at a given position, returning its entries in the order in which
+// they are stored, and the position after the row.
+
+fn decode_row_entries(v: &[u8], s: usize, storage_version: u32) -> (Vec<(i32, i32)>, usize) {
+    let mut all = Vec::<(i32, i32)>::new();
+    let w = if storage_version == 0 { 2 } else { 4 };
+    let get_w = |v: &[u8], pos: usize| {
+        if storage_version == 0 {
+            get_u16_at_pos(v, pos) as u32
+        } else {
+            get_u32_at_pos(v, pos)
+        }
+    };
+    let m1 = get_w(v, s) as usize;
+    let m2 = get_w(v, s + w) as usize;
+    let m4 = get_w(v, s + 2 * w) as usize;
+    let mut pos = s + 3 * w;
+    for _ in 0..m1 {
+        all.push((get_w(v, pos) as i32, get_u8_at_pos(v, pos + w) as i32));
+        pos += w + 1;
+    }
+    for _ in 0..m2 {
+        all.push((get_w(v, pos) as i32, get_u16_at_pos(v, pos + w) as i32));
+        pos += w + 2;
+    }
+    for _ in 0..m4 {
+        all.push((get_w(v, pos) as i32, get_u32_at_pos(v, pos + w) as i32));
+        pos += w + 4;
+    }
+    (all, pos)
+}
+
+const CURRENT_CODE_VERSION: usize = 2;
+
 impl MirrorSparseMatrix {
     pub fn new() -> MirrorSparseMatrix {
         let v = Vec::<u8>::new();
-        MirrorSparseMatrix { x: v }
+        MirrorSparseMatrix { x: Bytes::Owned(v) }
+    }
+
+    // Open a file written by write_to_file, by mapping it into memory.  Pages of the file are
+    // then read only as they are accessed.  Because of this, the checksum is not verified, as
+    // that would require reading the entire file.  The file must not be changed while the matrix
+    // is in use.
+
+    pub fn open_mmap(f: &str) -> MirrorSparseMatrix {
+        MirrorSparseMatrix::try_open_mmap(f)
+            .unwrap_or_else(|e| panic!("\nMirrorSparseMatrix: failed to read {}: {}.\n", f, e))
+    }
+
+    // Same as open_mmap, but return an error rather than panicking.
+
+    pub fn try_open_mmap(f: &str) -> Result<MirrorSparseMatrix, MirrorError> {
+        // The file begins with its length as a usize.
+        if std::mem::size_of::<usize>() != 8 {
+            return Err(MirrorError::UnsupportedUsize);
+        }
+        let ff = std::fs::File::open(f)?;
+        let m = unsafe { Mmap::map(&ff) }?;
+        if m.len() < 8 + MirrorSparseMatrix::header_size() {
+            return Err(MirrorError::Truncated);
+        }
+        let mut len = [0_u8; 8];
+        len.copy_from_slice(&m[0..8]);
+        if u64::from_ne_bytes(len) as usize != m.len() - 8 {
+            return Err(MirrorError::Truncated);
+        }
+        let s = MirrorSparseMatrix {
+            x: Bytes::Mapped(Arc::new(m)),
+        };
+        s.check_versions()?;
+        Ok(s)
+    }
+
+    fn check_versions(&self) -> Result<(), MirrorError> {
+        if self.code_version() > 2 {
+            return Err(MirrorError::BadCodeVersion(self.code_version() as u32));
+        }
+        if self.storage_version() != 0 && self.storage_version() != 1 {
+            return Err(MirrorError::BadStorageVersion(self.storage_version() as u32));
+        }
+        Ok(())
     }
 
     pub fn initialized(&self) -> bool {
@@ -161,6 +434,14 @@ impl MirrorSparseMatrix {
         + 4 // number of columns
     }
 
+    // Determine if the checksum at the end agrees with the rest of the data, assuming that the
+    // code version is at least 2.
+
+    fn checksum_ok(&self) -> bool {
+        let n = self.x.len();
+        n >= 4 && crc32(&self.x[0..n - 4]) == get_u32_at_pos(&self.x, n - 4)
+    }
+
     fn code_version(&self) -> usize {
         get_u32_at_pos(&self.x, 32) as usize
     }
@@ -216,10 +497,10 @@ impl MirrorSparseMatrix {
         for j in 0..k {
             total_bytes += col_labels[j].len();
         }
+        total_bytes += 4; // checksum
         v.reserve(total_bytes);
         v.append(&mut b"MirrorSparseMatrix binary file \n".to_vec());
         assert_eq!(v.len(), 32);
-        const CURRENT_CODE_VERSION: usize = 1;
         let code_version = CURRENT_CODE_VERSION as u32;
         push_u32(&mut v, code_version);
         push_u32(&mut v, storage_version);
@@ -252,55 +533,7 @@ impl MirrorSparseMatrix {
         for i in 0..n {
             let p = v.len() as u32;
             put_u32_at_pos(&mut v, hs + 4 * i, p);
-            let (mut m1, mut m2, mut m4) = (0, 0, 0);
-            for j in 0..x[i].len() {
-                if x[i][j].1 < 256 {
-                    m1 += 1;
-                } else if x[i][j].1 < 65536 {
-                    m2 += 1;
-                } else {
-                    m4 += 1;
-                }
-            }
-            if storage_version == 0 {
-                push_u16(&mut v, m1 as u16);
-                push_u16(&mut v, m2 as u16);
-                push_u16(&mut v, m4 as u16);
-            } else {
-                push_u32(&mut v, m1 as u32);
-                push_u32(&mut v, m2 as u32);
-                push_u32(&mut v, m4 as u32);
-            }
-            for j in 0..x[i].len() {
-                if x[i][j].1 < 256 {
-                    if storage_version == 0 {
-                        push_u16(&mut v, x[i][j].0 as u16);
-                    } else {
-                        push_u32(&mut v, x[i][j].0 as u32);
-                    }
-                    push_u8(&mut v, x[i][j].1 as u8);
-                }
-            }
-            for j in 0..x[i].len() {
-                if x[i][j].1 >= 256 && x[i][j].1 < 65536 {
-                    if storage_version == 0 {
-                        push_u16(&mut v, x[i][j].0 as u16);
-                    } else {
-                        push_u32(&mut v, x[i][j].0 as u32);
-                    }
-                    push_u16(&mut v, x[i][j].1 as u16);
-                }
-            }
-            for j in 0..x[i].len() {
-                if x[i][j].1 >= 65536 {
-                    if storage_version == 0 {
-                        push_u16(&mut v, x[i][j].0 as u16);
-                    } else {
-                        push_u32(&mut v, x[i][j].0 as u32);
-                    }
-                    push_u32(&mut v, x[i][j].1 as u32);
-                }
-            }
+            push_row_entries(&mut v, &x[i], storage_version);
         }
 
         // Insert row and column labels.
@@ -317,10 +550,89 @@ impl MirrorSparseMatrix {
             }
         }
 
-        // Done.
+        // Add checksum.  Done.
 
+        let crc = crc32(&v);
+        push_u32(&mut v, crc);
         assert_eq!(total_bytes, v.len());
-        MirrorSparseMatrix { x: v }
+        MirrorSparseMatrix { x: Bytes::Owned(v) }
+    }
+
+    // Build from (row, column, value) triplets, e.g. as read from a file in coordinate format.
+    // This gives the same result as build_from_vec would for the rows obtained by grouping the
+    // triplets by row.  If the triplets are grouped by row, in increasing order, then rows are
+    // encoded as they arrive, as for MirrorSparseMatrixBuilder, so the Vec<Vec<(i32, i32)>>
+    // representation is never created.  Otherwise, all the triplets are collected and sorted
+    // first, which takes twelve bytes per triplet of additional memory.  Each row must be
+    // < nrows, each column must be < ncols, and each value must be < 2^31.
+
+    pub fn build_from_triplets(
+        entries: impl Iterator<Item = (u32, u32, u32)>,
+        nrows: usize,
+        ncols: usize,
+        row_labels: &[String],
+        col_labels: &[String],
+    ) -> MirrorSparseMatrix {
+        assert_eq!(row_labels.len(), nrows);
+        assert_eq!(col_labels.len(), ncols);
+        let mut entries = entries.inspect(|&(row, col, value)| {
+            assert!(
+                (row as usize) < nrows,
+                "row {} is out of range, as there are {} rows",
+                row,
+                nrows
+            );
+            assert!(
+                (col as usize) < ncols,
+                "column {} is out of range, as there are {} columns",
+                col,
+                ncols
+            );
+            assert!(value <= i32::MAX as u32, "value {} is too large", value);
+        });
+        let mut b = MirrorSparseMatrixBuilder::new();
+        let mut row = Vec::<(i32, i32)>::new(); // entries for row b.row_starts.len()
+        while let Some(e) = entries.next() {
+            let i = e.0 as usize;
+            if i < b.row_starts.len() {
+                // The triplets are not grouped by row.  Recover the triplets seen so far, and
+                // start over with all the triplets, sorted.
+
+                let mut all = Vec::<(u32, u32, u32)>::new();
+                for r in 0..b.row_starts.len() {
+                    let (x, _) = decode_row_entries(&b.data, b.row_starts[r], b.storage_version);
+                    all.extend(
+                        x.iter()
+                            .map(|&(col, value)| (r as u32, col as u32, value as u32)),
+                    );
+                }
+                let r = b.row_starts.len() as u32;
+                all.extend(
+                    row.iter()
+                        .map(|&(col, value)| (r, col as u32, value as u32)),
+                );
+                all.push(e);
+                all.extend(entries);
+                all.sort_unstable_by_key(|e| (e.0, e.1));
+                return MirrorSparseMatrix::build_from_triplets(
+                    all.into_iter(),
+                    nrows,
+                    ncols,
+                    row_labels,
+                    col_labels,
+                );
+            }
+            while b.row_starts.len() < i {
+                b.push_row(&row, &row_labels[b.row_starts.len()]);
+                row.clear();
+            }
+            row.push((e.1 as i32, e.2 as i32));
+        }
+        while b.row_starts.len() < nrows {
+            b.push_row(&row, &row_labels[b.row_starts.len()]);
+            row.clear();
+        }
+        b.finish(col_labels)
     }
 
     pub fn nrows(&self) -> usize {
@@ -404,6 +716,43 @@ impl MirrorSparseMatrix {
         all
     }
 
+    // Iterate over the rows, in order, giving for each the same as row() would.  Rows are stored
+    // one after another, so this just walks through them, rather than looking up the start of
+    // each row.
+
+    pub fn iter_rows(&self) -> impl Iterator<Item = Vec<(usize, usize)>> + '_ {
+        let n = self.nrows();
+        let storage_version = self.storage_version() as u32;
+        let mut pos = if n == 0 { 0 } else { self.start_of_row(0) };
+        (0..n).map(move |_| {
+            let (entries, next) = decode_row_entries(&self.x, pos, storage_version);
+            pos = next;
+            let mut all = entries
+                .into_iter()
+                .map(|(col, entry)| (col as u32 as usize, entry as u32 as usize))
+                .collect::<Vec<_>>();
+            all.sort_unstable();
+            all
+        })
+    }
+
+    // Iterate over all the entries (row, col, value) that are stored in the matrix, in row-major
+    // order.  This reads the bytes directly and does not allocate, so it is the fast way to make a
+    // pass through the whole matrix.  Within a row, entries are stored in three groups, according
+    // to the size of their value, and the groups are merged, so the order within a row is by
+    // column, except possibly for files of code version 1, which need not be sorted.
+
+    pub fn iter_entries(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        EntryIter {
+            m: self,
+            w: if self.storage_version() == 0 { 2 } else { 4 },
+            row: 0,
+            next_row: 0,
+            pos: [0; 3],
+            left: [0; 3],
+        }
+    }
+
     pub fn sum_of_row(&self, row: usize) -> usize {
         let s = self.start_of_row(row);
         let mut sum = 0;
@@ -505,61 +854,228 @@ impl MirrorSparseMatrix {
         sum
     }
 
+    // Return the value of a matrix entry.
+
     pub fn value(&self, row: usize, col: usize) -> usize {
+        self.stored_value(row, col).unwrap_or(0)
+    }
+
+    // Return the (row, value) pairs for the entries that are stored in a given column, sorted by
+    // row.  As for row(), this may include entries whose value is zero.
+
+    pub fn col(&self, col: usize) -> Vec<(usize, usize)> {
+        (0..self.nrows())
+            .filter_map(|row| self.stored_value(row, col).map(|v| (row, v)))
+            .collect()
+    }
+
+    // Return the value of a matrix entry, or None if it is not stored.  Within each of the three
+    // groups of entries in a row, entries are stored in increasing order by column, so binary
+    // search can be used.  This is not guaranteed for code version 1, in which entries are stored
+    // in the order given when the matrix was built, so in that case a linear search is used.
+
+    fn stored_value(&self, row: usize, col: usize) -> Option<usize> {
         let s = self.start_of_row(row);
-        if self.storage_version() == 0 {
-            let m1 = get_u16_at_pos(&self.x, s) as usize;
-            let m2 = get_u16_at_pos(&self.x, s + 2) as usize;
-            let m4 = get_u16_at_pos(&self.x, s + 4) as usize;
-            for i in 0..m1 {
-                let pos = s + 6 + 3 * i;
-                let f = get_u16_at_pos(&self.x, pos) as usize;
-                if f == col {
-                    return get_u8_at_pos(&self.x, pos + 2) as usize;
-                }
+        let w = if self.storage_version() == 0 { 2 } else { 4 };
+        let get_w = |pos: usize| {
+            if w == 2 {
+                get_u16_at_pos(&self.x, pos) as usize
+            } else {
+                get_u32_at_pos(&self.x, pos) as usize
             }
-            for i in 0..m2 {
-                let pos = s + 6 + 3 * m1 + 4 * i;
-                let f = get_u16_at_pos(&self.x, pos) as usize;
-                if f == col {
-                    return get_u16_at_pos(&self.x, pos + 2) as usize;
+        };
+        let get_value = |pos: usize, vw: usize| match vw {
+            1 => get_u8_at_pos(&self.x, pos) as usize,
+            2 => get_u16_at_pos(&self.x, pos) as usize,
+            _ => get_u32_at_pos(&self.x, pos) as usize,
+        };
+        let sorted = self.code_version() >= 2;
+        let (m1, m2, m4) = (get_w(s), get_w(s + w), get_w(s + 2 * w));
+        let mut start = s + 3 * w;
+        for &(m, vw) in [(m1, 1), (m2, 2), (m4, 4)].iter() {
+            if !sorted {
+                for i in 0..m {
+                    let pos = start + i * (w + vw);
+                    if get_w(pos) == col {
+                        return Some(get_value(pos + w, vw));
+                    }
                 }
-            }
-            for i in 0..m4 {
-                let pos = s + 6 + 3 * m1 + 4 * m2 + 6 * i;
-                let f = get_u16_at_pos(&self.x, pos) as usize;
-                if f == col {
-                    return get_u32_at_pos(&self.x, pos + 2) as usize;
+            } else {
+                let (mut lo, mut hi) = (0, m);
+                while lo < hi {
+                    let mid = (lo + hi) / 2;
+                    let pos = start + mid * (w + vw);
+                    let f = get_w(pos);
+                    if f == col {
+                        return Some(get_value(pos + w, vw));
+                    } else if f < col {
+                        lo = mid + 1;
+                    } else {
+                        hi = mid;
+                    }
                 }
             }
-            0
+            start += m * (w + vw);
+        }
+        None
+    }
+}
+
+// EntryIter: the iterator returned by iter_entries.  For the current row, pos[g] is the position
+// of the next entry in group g (values stored as u8, u16 and u32, for g = 0, 1, 2), and left[g]
+// is the number of entries remaining in that group.
+
+struct EntryIter<'a> {
+    m: &'a MirrorSparseMatrix,
+    w: usize, // number of bytes used to store a column or count
+    row: usize,
+    next_row: usize,
+    pos: [usize; 3],
+    left: [usize; 3],
+}
+
+impl<'a> EntryIter<'a> {
+    fn get_w(&self, pos: usize) -> usize {
+        if self.w == 2 {
+            get_u16_at_pos(&self.m.x, pos) as usize
         } else {
-            let m1 = get_u32_at_pos(&self.x, s) as usize;
-            let m2 = get_u32_at_pos(&self.x, s + 4) as usize;
-            let m4 = get_u32_at_pos(&self.x, s + 8) as usize;
-            for i in 0..m1 {
-                let pos = s + 12 + 5 * i;
-                let f = get_u32_at_pos(&self.x, pos) as usize;
-                if f == col {
-                    return get_u8_at_pos(&self.x, pos + 4) as usize;
-                }
+            get_u32_at_pos(&self.m.x, pos) as usize
+        }
+    }
+}
+
+impl<'a> Iterator for EntryIter<'a> {
+    type Item = (usize, usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize, usize)> {
+        while self.left == [0; 3] {
+            if self.next_row == self.m.nrows() {
+                return None;
             }
-            for i in 0..m2 {
-                let pos = s + 12 + 5 * m1 + 6 * i;
-                let f = get_u32_at_pos(&self.x, pos) as usize;
-                if f == col {
-                    return get_u16_at_pos(&self.x, pos + 4) as usize;
-                }
+            self.row = self.next_row;
+            self.next_row += 1;
+            let s = self.m.start_of_row(self.row);
+            let w = self.w;
+            for g in 0..3 {
+                self.left[g] = self.get_w(s + g * w);
             }
-            for i in 0..m4 {
-                let pos = s + 12 + 5 * m1 + 6 * m2 + 8 * i;
-                let f = get_u32_at_pos(&self.x, pos) as usize;
-                if f == col {
-                    return get_u32_at_pos(&self.x, pos + 4) as usize;
+            self.pos[0] = s + 3 * w;
+            self.pos[1] = self.pos[0] + (w + 1) * self.left[0];
+            self.pos[2] = self.pos[1] + (w + 2) * self.left[1];
+        }
+        let mut best: Option<(usize, usize)> = None;
+        for g in 0..3 {
+            if self.left[g] > 0 {
+                let col = self.get_w(self.pos[g]);
+                if best.is_none() || col < best.unwrap().1 {
+                    best = Some((g, col));
                 }
             }
-            0
         }
+        let (g, col) = best.unwrap();
+        let vpos = self.pos[g] + self.w;
+        let value = match g {
+            0 => get_u8_at_pos(&self.m.x, vpos) as usize,
+            1 => get_u16_at_pos(&self.m.x, vpos) as usize,
+            _ => get_u32_at_pos(&self.m.x, vpos) as usize,
+        };
+        self.pos[g] = vpos + (1 << g);
+        self.left[g] -= 1;
+        Some((self.row, col, value))
+    }
+}
+
+// MirrorSparseMatrixBuilder: build a MirrorSparseMatrix incrementally, one row at a time.
+// The result is identical to what build_from_vec would give for the same rows.  Rows are
+// encoded as they arrive, so the memory used is roughly the size of the final matrix, rather
+// than that plus the size of the Vec<Vec<(i32, i32)>> representation.  The storage version is
+// not known until the end, so rows are encoded using storage version 0, and reencoded if a
+// column ≥ 2^16 is encountered.
+
+#[derive(Default)]
+pub struct MirrorSparseMatrixBuilder {
+    data: Vec<u8>,          // encoded rows
+    row_starts: Vec<usize>, // start of each row in data
+    row_labels: Vec<String>,
+    storage_version: u32,
+}
+
+impl MirrorSparseMatrixBuilder {
+    pub fn new() -> MirrorSparseMatrixBuilder {
+        MirrorSparseMatrixBuilder::default()
+    }
+
+    pub fn push_row(&mut self, entries: &[(i32, i32)], label: &str) {
+        if self.storage_version == 0 && entries.iter().any(|e| e.0 >= 65536) {
+            self.upgrade_storage_version();
+        }
+        self.row_starts.push(self.data.len());
+        push_row_entries(&mut self.data, entries, self.storage_version);
+        self.row_labels.push(label.to_string());
+    }
+
+    // Reencode the rows seen so far using storage version 1.
+
+    fn upgrade_storage_version(&mut self) {
+        let mut data = Vec::<u8>::new();
+        for i in 0..self.row_starts.len() {
+            let (row, _) = decode_row_entries(&self.data, self.row_starts[i], 0);
+            self.row_starts[i] = data.len();
+            push_row_entries(&mut data, &row, 1);
+        }
+        self.data = data;
+        self.storage_version = 1;
+    }
+
+    pub fn finish(self, col_labels: &[String]) -> MirrorSparseMatrix {
+        let (n, k) = (self.row_starts.len(), col_labels.len());
+        let hs = MirrorSparseMatrix::header_size();
+
+        // Create the part that precedes the rows.
+
+        let head_bytes = hs + 4 * n + 4 * (1 + n) + 4 * (1 + k);
+        let mut head = Vec::<u8>::with_capacity(head_bytes);
+        head.append(&mut b"MirrorSparseMatrix binary file \n".to_vec());
+        push_u32(&mut head, CURRENT_CODE_VERSION as u32);
+        push_u32(&mut head, self.storage_version);
+        push_u32(&mut head, n as u32);
+        push_u32(&mut head, k as u32);
+        for i in 0..n {
+            push_u32(&mut head, (head_bytes + self.row_starts[i]) as u32);
+        }
+        let mut pos = head_bytes + self.data.len();
+        for i in 0..=n {
+            push_u32(&mut head, pos as u32);
+            if i < n {
+                pos += self.row_labels[i].len();
+            }
+        }
+        for j in 0..=k {
+            push_u32(&mut head, pos as u32);
+            if j < k {
+                pos += col_labels[j].len();
+            }
+        }
+        assert_eq!(head.len(), head_bytes);
+
+        // Shift the rows over in place, put the head in front, and append the labels.
+
+        let mut v = self.data;
+        let data_bytes = v.len();
+        v.reserve_exact(pos + 4 - data_bytes);
+        v.resize(head_bytes + data_bytes, 0);
+        v.copy_within(0..data_bytes, head_bytes);
+        v[0..head_bytes].copy_from_slice(&head);
+        for label in self.row_labels.iter() {
+            v.extend_from_slice(label.as_bytes());
+        }
+        for label in col_labels.iter() {
+            v.extend_from_slice(label.as_bytes());
+        }
+        let crc = crc32(&v);
+        push_u32(&mut v, crc);
+        assert_eq!(pos + 4, v.len());
+        MirrorSparseMatrix { x: Bytes::Owned(v) }
     }
 }
 
@@ -578,6 +1094,12 @@ mod tests {
     use io_utils::printme;
     use pretty_trace::PrettyTrace;
 
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
     #[test]
     fn test_mirror_sparse_matrix() {
         PrettyTrace::new().on();
@@ -628,11 +1150,231 @@ mod tests {
                 }
             }
             let col_sum2 = y.sum_of_col(test_col);
+            assert_eq!(y.col(test_col).iter().map(|e| e.1).sum::<usize>(), col_sum2);
             printme!(col_sum, col_sum2);
             assert_eq!(col_sum, col_sum2);
             assert_eq!(y.storage_version(), storage_version);
             assert_eq!(y.row_label(5), row_labels[5]);
             assert_eq!(y.col_label(7), col_labels[7]);
+
+            // Check iteration over all entries.
+
+            let total: usize = (0..y.nrows()).map(|i| y.sum_of_row(i)).sum();
+            assert_eq!(y.iter_entries().map(|e| e.2).sum::<usize>(), total);
+            let rows = y.iter_rows().collect::<Vec<_>>();
+            assert_eq!(rows.len(), y.nrows());
+            for (i, row) in rows.iter().enumerate() {
+                assert_eq!(*row, y.row(i));
+            }
+
+            // Check that building incrementally gives the same bytes.
+
+            let mut b = MirrorSparseMatrixBuilder::new();
+            for i in 0..n {
+                b.push_row(&x[i as usize], &row_labels[i as usize]);
+            }
+            assert_eq!(b.finish(&col_labels).x[..], y.x[..]);
+
+            // Check that building from triplets, given in row-major or column-major order, or
+            // with the order within each row reversed, gives the same bytes.  Building from
+            // triplets requires a label for each column.
+
+            let ncols = x.iter().flatten().map(|e| e.0 as usize + 1).max().unwrap();
+            let all_col_labels = (0..ncols).map(|j| j.to_string()).collect::<Vec<_>>();
+            let yy = MirrorSparseMatrix::build_from_vec(&x, &row_labels, &all_col_labels);
+            let mut triplets = Vec::<(u32, u32, u32)>::new();
+            for (i, row) in x.iter().enumerate() {
+                for &(col, value) in row.iter() {
+                    triplets.push((i as u32, col as u32, value as u32));
+                }
+            }
+            let mut column_major = triplets.clone();
+            column_major.sort_by_key(|e| (e.1, e.0));
+            let mut reversed = triplets.clone();
+            reversed.sort_by_key(|e| (e.0, std::cmp::Reverse(e.1)));
+            for t in [triplets, column_major, reversed] {
+                let z = MirrorSparseMatrix::build_from_triplets(
+                    t.into_iter(),
+                    n as usize,
+                    ncols,
+                    &row_labels,
+                    &all_col_labels,
+                );
+                assert_eq!(z.x[..], yy.x[..]);
+            }
+
+            // Check writing and reading, and that corruption would be detected.
+
+            let f = std::env::temp_dir().join(format!("mirror_sparse_matrix_{}", storage_version));
+            let f = f.to_str().unwrap();
+            write_to_file(&y, f);
+            assert_eq!(get_code_version_from_file(f), 2);
+            let mut w = MirrorSparseMatrix::new();
+            read_from_file(&mut w, f);
+            assert_eq!(w.x[..], y.x[..]);
+            let m = MirrorSparseMatrix::open_mmap(f);
+            assert_eq!(m.x[..], y.x[..]);
+            assert_eq!(m.value(9, 50), y.value(9, 50));
+            std::fs::remove_file(f).unwrap();
+            assert!(m.checksum_ok());
+            let mut v = w.x.to_vec();
+            v[1000] ^= 1;
+            assert!(!MirrorSparseMatrix {
+                x: Bytes::Owned(v.clone())
+            }
+            .checksum_ok());
+            v.truncate(2000);
+            assert!(!MirrorSparseMatrix { x: Bytes::Owned(v) }.checksum_ok());
+
+            // Check the errors returned when reading fails.
+
+            let mut v = w.x.to_vec();
+            v[32] = 7;
+            try_write_to_file(&MirrorSparseMatrix { x: Bytes::Owned(v) }, f).unwrap();
+            let mut w = MirrorSparseMatrix::new();
+            let e = try_read_from_file(&mut w, f).unwrap_err();
+            assert!(matches!(e, MirrorError::BadCodeVersion(7)));
+            let mut v = y.x.to_vec();
+            v[40] ^= 1;
+            try_write_to_file(&MirrorSparseMatrix { x: Bytes::Owned(v) }, f).unwrap();
+            let e = try_read_from_file(&mut w, f).unwrap_err();
+            assert!(matches!(e, MirrorError::BadChecksum));
+            write_to_file(&y, f);
+            let g = std::fs::read(f).unwrap();
+            std::fs::write(f, &g[..g.len() - 1]).unwrap();
+            assert!(matches!(
+                MirrorSparseMatrix::try_open_mmap(f),
+                Err(MirrorError::Truncated)
+            ));
+            std::fs::write(f, [1, 0, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+            let e = try_read_from_file(&mut w, f).unwrap_err();
+            assert!(matches!(e, MirrorError::Truncated));
+            assert!(matches!(
+                MirrorSparseMatrix::try_open_mmap(f),
+                Err(MirrorError::Truncated)
+            ));
+            std::fs::remove_file(f).unwrap();
+            let e = try_read_from_file(&mut w, f).unwrap_err();
+            assert!(matches!(e, MirrorError::Io(_)));
+            assert!(matches!(
+                MirrorSparseMatrix::try_open_mmap(f),
+                Err(MirrorError::Io(_))
+            ));
+
+            // Check that a failed read leaves the matrix unchanged, and that an uninitialized
+            // matrix cannot be written.
+
+            let mut w = y.clone();
+            assert!(try_read_from_file(&mut w, f).is_err());
+            assert_eq!(w.x[..], y.x[..]);
+            let e = try_write_to_file(&MirrorSparseMatrix::new(), f).unwrap_err();
+            assert!(matches!(e, MirrorError::Uninitialized));
+        }
+
+        // Check incremental building in the case where a big column first appears late.
+
+        let x = vec![
+            vec![(1, 5), (3, 300), (4, 70000)],
+            vec![],
+            vec![(2, 1), (70000, 2)],
+        ];
+        let row_labels = vec!["a".to_string(), "bb".to_string(), "ccc".to_string()];
+        let col_labels = vec!["x".to_string(), "y".to_string()];
+        let y = MirrorSparseMatrix::build_from_vec(&x, &row_labels, &col_labels);
+        let mut b = MirrorSparseMatrixBuilder::new();
+        for i in 0..x.len() {
+            b.push_row(&x[i], &row_labels[i]);
+        }
+        assert_eq!(b.finish(&col_labels).x[..], y.x[..]);
+        let mut entries = Vec::<(usize, usize, usize)>::new();
+        for i in 0..y.nrows() {
+            for (col, value) in y.row(i) {
+                entries.push((i, col, value));
+            }
+        }
+        assert_eq!(y.iter_entries().collect::<Vec<_>>(), entries);
+
+        // Check that rows not given in order by column are stored sorted, so that value and col
+        // find their entries.
+
+        let x = vec![
+            vec![(7, 1), (2, 300), (5, 2), (0, 70000), (3, 400)],
+            vec![(1, 9)],
+        ];
+        let row_labels = vec!["a".to_string(), "b".to_string()];
+        let y = MirrorSparseMatrix::build_from_vec(&x, &row_labels, &col_labels);
+        let mut b = MirrorSparseMatrixBuilder::new();
+        for i in 0..x.len() {
+            b.push_row(&x[i], &row_labels[i]);
+        }
+        assert_eq!(b.finish(&col_labels).x[..], y.x[..]);
+        for (i, row) in x.iter().enumerate() {
+            for &(col, value) in row.iter() {
+                assert_eq!(y.value(i, col as usize), value as usize);
+            }
+        }
+        assert_eq!(y.value(0, 4), 0);
+        assert_eq!(y.col(5), vec![(0, 2)]);
+        assert_eq!(
+            y.iter_entries().collect::<Vec<_>>(),
+            vec![
+                (0, 0, 70000),
+                (0, 2, 300),
+                (0, 3, 400),
+                (0, 5, 2),
+                (0, 7, 1),
+                (1, 1, 9)
+            ]
+        );
+
+        // Check value on random matrices, versus a linear search of each row.
+
+        let mut r = 1_u64;
+        let mut rand = |m: u64| {
+            r = r
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (r >> 33) % m
+        };
+        for pass in 0..6 {
+            let gap = if pass % 2 == 0 { 10 } else { 50000 };
+            let mut x = Vec::<Vec<(i32, i32)>>::new();
+            for _ in 0..20 {
+                let mut row = Vec::<(i32, i32)>::new();
+                let mut col = rand(gap) as i32;
+                for _ in 0..rand(40) {
+                    let value = match rand(3) {
+                        0 => rand(256),
+                        1 => rand(65536),
+                        _ => rand(1 << 30),
+                    };
+                    row.push((col, value as i32));
+                    col += 1 + rand(gap) as i32;
+                }
+                x.push(row);
+            }
+            let row_labels = vec![String::new(); x.len()];
+            let y = MirrorSparseMatrix::build_from_vec(&x, &row_labels, &[]);
+            for (i, row) in x.iter().enumerate() {
+                let linear = y.row(i);
+                let max_col = row.last().map_or(0, |e| e.0 as usize);
+                for col in (0..=max_col + 1).step_by(1 + max_col / 5000) {
+                    let v = linear.iter().find(|e| e.0 == col).map_or(0, |e| e.1);
+                    assert_eq!(y.value(i, col), v);
+                }
+                for &(col, value) in row.iter() {
+                    assert_eq!(y.value(i, col as usize), value as usize);
+                }
+            }
+            for col in 0..20 {
+                let mut entries = Vec::<(usize, usize)>::new();
+                for (i, row) in x.iter().enumerate() {
+                    if let Some(e) = row.iter().find(|e| e.0 as usize == col) {
+                        entries.push((i, e.1 as usize));
+                    }
+                }
+                assert_eq!(y.col(col), entries);
+            }
         }
     }
 }
@@ -10,6 +10,7 @@ use libc::{getuid, rusage, RLIMIT_NPROC};
 #[cfg(not(target_os = "windows"))]
 use libc::{rlimit, setrlimit};
 
+use serde::Serialize;
 use std::{
     cmp::min,
     fs::File,
@@ -70,6 +71,67 @@ pub fn set_max_threads(n: u64) {
     }
 }
 
+// Restrict execution to the given set of CPUs (numbered from zero).  Like set_max_threads, this
+// is intended for controlling the execution environment, e.g. to get reproducible benchmarks.
+// This sets the affinity of the calling thread, and threads spawned afterwards inherit it, so
+// calling it at the start of main (before any threads are created) pins the whole process.
+// Threads that already exist are not affected.  On non-linux platforms this does nothing
+// and returns an error.
+//
+// get_cpu_affinity returns the sorted list of CPUs that the calling thread may run on.  On
+// non-linux platforms, or if the list cannot be obtained, it returns an empty list.
+
+#[cfg(target_os = "linux")]
+pub fn set_cpu_affinity(cpus: &[usize]) -> Result<(), String> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        for &cpu in cpus {
+            if cpu >= libc::CPU_SETSIZE as usize {
+                return Err(format!(
+                    "cpu {} exceeds the maximum of {}",
+                    cpu,
+                    libc::CPU_SETSIZE - 1
+                ));
+            }
+            libc::CPU_SET(cpu, &mut set);
+        }
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(format!(
+                "sched_setaffinity failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_cpu_affinity() -> Vec<usize> {
+    let mut cpus = Vec::<usize>::new();
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) == 0 {
+            for cpu in 0..libc::CPU_SETSIZE as usize {
+                if libc::CPU_ISSET(cpu, &set) {
+                    cpus.push(cpu);
+                }
+            }
+        }
+    }
+    cpus
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_cpu_affinity(_cpus: &[usize]) -> Result<(), String> {
+    Err("set_cpu_affinity is only implemented for linux".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn get_cpu_affinity() -> Vec<usize> {
+    Vec::new()
+}
+
 // Report peak memory usage in bytes or gigabytes.  For linux, this is determined by reading the
 // proc filesystem.
 
@@ -156,6 +218,23 @@ pub fn available_mem_gb() -> Option<f64> {
     }
 }
 
+// Report the number of open file descriptors, by counting the entries in /proc/self/fd.
+// Note that this includes the descriptor used to read that directory.  Returns None on
+// non-linux platforms.
+
+#[cfg(target_os = "linux")]
+pub fn open_fd_count() -> Option<usize> {
+    if !std::path::Path::new("/proc/self/fd").is_dir() {
+        return None;
+    }
+    Some(dir_list("/proc/self/fd").len())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_fd_count() -> Option<usize> {
+    None
+}
+
 // Report getrusage stats.
 
 #[cfg(not(target_os = "windows"))]
@@ -165,6 +244,23 @@ pub fn getrusage() -> rusage {
     usage
 }
 
+// Return the page size in bytes.  If it cannot be found, assume that it is 4096.
+
+#[cfg(not(target_os = "windows"))]
+fn page_size() -> i64 {
+    let n = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    if n > 0 {
+        n as i64
+    } else {
+        4096
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn page_size() -> i64 {
+    4096
+}
+
 // Return current memory usage.
 
 pub fn mem_usage_bytes() -> i64 {
@@ -180,9 +276,7 @@ pub fn mem_usage_bytes() -> i64 {
             if let Some(line) = f.lines().next() {
                 let s = line.unwrap();
                 let fields: Vec<&str> = s.split_whitespace().collect();
-                // ◼ The page size should not be hardcoded.
-                let page_size = 4096_i64;
-                return fields[1].force_i64() * page_size;
+                return fields[1].force_i64() * page_size();
             }
         }
     };
@@ -193,6 +287,26 @@ pub fn mem_usage_gb() -> f64 {
     mem_usage_bytes() as f64 / ((1024 * 1024 * 1024) as f64)
 }
 
+// A snapshot of the stats above, e.g. for logging as JSON.  Memory values are in gigabytes.
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SystemSnapshot {
+    pub nthreads: i64,
+    pub rss_gb: f64,
+    pub peak_rss_gb: f64,
+    pub available_mem_gb: Option<f64>,
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "linux"))]
+pub fn snapshot() -> SystemSnapshot {
+    SystemSnapshot {
+        nthreads: nthreads(),
+        rss_gb: mem_usage_gb(),
+        peak_rss_gb: peak_mem_usage_gb(),
+        available_mem_gb: available_mem_gb(),
+    }
+}
+
 // Report the status of all processes having the same owner as 'this' process,
 // showing the parent process id, the process id, its memory use in GB (RSS),
 // and the command, which is folder.
@@ -274,3 +388,68 @@ pub fn ps_me() {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p perf_stats
+
+    // Every test here is Linux-only, so the imports are too.
+
+    #[cfg(target_os = "linux")]
+    use crate::{
+        get_cpu_affinity, mem_usage_bytes, open_fd_count, page_size, set_cpu_affinity, snapshot,
+    };
+    #[cfg(target_os = "linux")]
+    use std::fs::File;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_snapshot() {
+        let s = snapshot();
+        assert!(s.nthreads >= 1);
+        assert!(s.rss_gb > 0.0);
+        assert!(s.peak_rss_gb > 0.0);
+        if let Some(gb) = s.available_mem_gb {
+            assert!(gb >= 0.0);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_mem_usage_bytes() {
+        let p = page_size();
+        assert!(p >= 4096 && p.count_ones() == 1);
+        let m = mem_usage_bytes();
+        assert!(m > 0 && m % p == 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cpu_affinity() {
+        let cpus = get_cpu_affinity();
+        assert!(!cpus.is_empty());
+        set_cpu_affinity(&cpus[0..1]).unwrap();
+        assert_eq!(get_cpu_affinity(), cpus[0..1].to_vec());
+        set_cpu_affinity(&cpus).unwrap();
+        assert_eq!(get_cpu_affinity(), cpus);
+        assert!(set_cpu_affinity(&[1 << 20]).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_open_fd_count() {
+        // Other tests may open files concurrently, so allow some slop.
+        const K: usize = 100;
+        let n1 = open_fd_count().unwrap();
+        let files = (0..K)
+            .map(|_| File::open("/proc/self/status").unwrap())
+            .collect::<Vec<_>>();
+        let n2 = open_fd_count().unwrap();
+        assert!(n2 >= n1 + K / 2);
+        drop(files);
+        let n3 = open_fd_count().unwrap();
+        assert!(n3 + K / 2 <= n2);
+    }
+}
@@ -191,7 +191,7 @@ use string_utils::{stringme, strme, TextUtils};
 #[cfg(all(not(target_os = "windows"), feature = "pprof"))]
 use tables::print_tabular_vbox;
 #[cfg(all(not(target_os = "windows"), feature = "pprof"))]
-use vector_utils::make_freq;
+use vector_utils::make_freq_ref;
 use vector_utils::{contains_at, erase_if};
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -329,7 +329,7 @@ pub fn stop_profiling() {
             }
             if !symv.is_empty() {
                 let mut log = String::new();
-                print_tabular_vbox(&mut log, &symv, 0, b"l|l|l|l|l", false, false);
+                print_tabular_vbox(&mut log, &symv, 0, b"l|l|l|l|l", false, false, false);
                 for _ in 0..*count {
                     let x = log.to_string();
                     traces.push(x);
@@ -337,8 +337,8 @@ pub fn stop_profiling() {
             }
         }
         traces.sort();
-        let mut freq = Vec::<(u32, String)>::new();
-        make_freq(&traces, &mut freq);
+        let mut freq = Vec::<(u32, &String)>::new();
+        make_freq_ref(&traces, &mut freq);
         let mut report = String::with_capacity(61 + 40 * freq.len());
         let traced = 100.0 * traces.len() as f64 / n as f64;
         write!(
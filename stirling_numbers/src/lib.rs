@@ -6,7 +6,8 @@
 //! is the number of <code>k</code>-element partitions of a set of size <code>n</code>.
 //! See <a href="https://en.wikipedia.org/wiki/Stirling_numbers_of_the_second_kind">wikipedia</a>.
 //!
-//! This crate consists of a few functions related to these Stirling numbers.
+//! This crate consists of a few functions related to these Stirling numbers, plus a table of
+//! the unsigned Stirling numbers of the first kind.
 
 #[cfg(not(debug_assertions))]
 #[cfg(test)]
@@ -67,6 +68,101 @@ pub fn stirling2_table<T: Num + Clone + From<u32>>(n_max: usize) -> Vec<Vec<T>>
     s
 }
 
+/// Compute a single Stirling number of the second kind <code>S(n,k)</code>, without building
+/// the table.
+/// <br>&nbsp;
+///
+/// <b>Method.</b>  Use the same recurrence relation as <code>stirling2_table</code>, keeping
+/// only the entries <code>S(m,j)</code> for <code>j ≤ k</code> of the current row
+/// <code>m</code>, and updating them in place for <code>m = 1, ..., n</code>.  If
+/// <code>k > n</code>, the value is zero.
+///
+/// <b>Computational complexity.</b>  <code>O(n*k)</code> time and <code>O(k)</code> space,
+/// assuming that <code>T</code> is a fixed-size type like <code>f64</code>.
+///
+/// <b>Testing.</b>  We check that the values agree with <code>stirling2_table</code>, for
+/// <code>f64</code> and <code>BigUint</code>.
+
+pub fn stirling2<T: Num + Clone + From<u32>>(n: usize, k: usize) -> T {
+    if k > n {
+        return Zero::zero();
+    }
+    let mut s = vec![T::zero(); k + 1];
+    s[0] = One::one();
+    for m in 1..=n {
+        for j in (1..=k.min(m)).rev() {
+            s[j] = T::from(j as u32) * s[j].clone() + s[j - 1].clone();
+        }
+        s[0] = Zero::zero();
+    }
+    s[k].clone()
+}
+
+/// Compute the Bell numbers <code>B(n)</code>, for <code>n ≤ n_max</code>.  The number
+/// <code>B(n)</code> is the number of partitions of a set of size <code>n</code>, so it is the
+/// sum of the Stirling numbers <code>S(n,k)</code> over <code>0 ≤ k ≤ n</code>.  See
+/// <a href="https://en.wikipedia.org/wiki/Bell_number">wikipedia</a>.
+/// <br>&nbsp;
+///
+/// <b>Method.</b>  Build the table using <code>stirling2_table</code> and sum each row.
+///
+/// <b>Computational complexity.</b>  <code>O(n_max^2)</code> assuming that <code>T</code>
+/// is a fixed-size type like <code>f64</code>.
+///
+/// <b>Testing and accuracy.</b>  For <code>T = f64</code>, each value is a sum of many rounded
+/// table entries, so its error accumulates, and the values become inexact before the table
+/// entries themselves do: <code>B(n)</code> is exact only for <code>n ≤ 22</code>.  Use
+/// <code>BigUint</code> if exact values are needed.  We check the first dozen values versus
+/// exact values.
+
+pub fn bell_numbers<T: Num + Clone + From<u32>>(n_max: usize) -> Vec<T> {
+    let s = stirling2_table::<T>(n_max);
+    s.iter()
+        .map(|row| row.iter().fold(Zero::zero(), |sum: T, x| sum + x.clone()))
+        .collect()
+}
+
+/// Build a table of unsigned Stirling numbers of the first kind <code>c(n,k)</code>, for
+/// <code>n ≤ n_max</code>.  The number <code>c(n,k)</code> is the number of permutations of a set
+/// of size <code>n</code> having exactly <code>k</code> cycles.  See
+/// <a href="https://en.wikipedia.org/wiki/Stirling_numbers_of_the_first_kind">wikipedia</a>.
+/// <br>&nbsp;
+///
+/// <b>Method.</b>  Use the recurrence relation:
+/// <pre>
+/// c(n,0) = delta(0,n)
+/// c(n,n) = 1
+/// c(n,k) = c(n-1,k-1) + (n-1) * c(n-1,k) if 1 ≤ k < n.
+/// </pre>
+/// Reasonable choices for <code>T</code> are <code>f64</code> and <code>BigUint</code>, which
+/// are exact integers.  Since row <code>n</code> sums to <code>n!</code>, entries grow much
+/// faster than for <code>stirling2_table</code>, and for <code>T = f64</code> some are infinite
+/// once <code>n_max > 170</code>.
+///
+/// <b>Computational complexity.</b>  <code>O(n_max^2)</code> assuming that <code>T</code>
+/// is a fixed-size type like <code>f64</code>.
+///
+/// <b>Testing.</b>  We check one table entry versus wikipedia, and verify using exact
+/// arithmetic that each row sums to <code>n!</code>.
+
+pub fn stirling1_table<T: Num + Clone + From<u32>>(n_max: usize) -> Vec<Vec<T>> {
+    let mut c = Vec::<Vec<T>>::new();
+    let zero: T = Zero::zero();
+    let one: T = One::one();
+    for n in 0..=n_max {
+        c.push(vec![zero.clone(); n + 1]);
+    }
+    c[0][0] = one.clone();
+    for n in 1..=n_max {
+        c[n][0] = zero.clone();
+        for k in 1..n {
+            c[n][k] = c[n - 1][k - 1].clone() + T::from((n - 1) as u32) * c[n - 1][k].clone();
+        }
+        c[n][n] = one.clone();
+    }
+    c
+}
+
 /// Compute a table of "Stirling ratios", Stirling numbers divided by the asympotic approximation
 /// <code>k^n / k!</code>, which is useful because the ratios are numerically better behaved than
 /// the Stirling numbers themselves.
@@ -173,15 +269,10 @@ pub fn p_at_most_m_distinct_in_sample_of_x_from_n(
     sr: &[Vec<f64>],
 ) -> f64 {
     let mut p = 1.0;
-    for u in m + 1..=x {
-        let mut z = sr[x][u];
-        for _ in 0..x {
-            z *= u as f64 / n as f64;
-        }
-        for v in 1..=u {
-            z *= (n - v + 1) as f64 / (u - v + 1) as f64;
-        }
-        p -= z;
+    // At most n distinct elements can be selected, so there is nothing to subtract for u > n.
+
+    for u in m + 1..=x.min(n) {
+        p -= p_exactly_u_distinct(u, x, n, sr);
     }
     if p < 0.0 {
         p = 0.0;
@@ -189,9 +280,159 @@ pub fn p_at_most_m_distinct_in_sample_of_x_from_n(
     p
 }
 
+// The probability Z(u,x,n) = SR(x,u) * (u/n)^x * choose(n,u) of selecting exactly u distinct
+// elements, as above.  Requires u ≤ n.
+
+fn p_exactly_u_distinct(u: usize, x: usize, n: usize, sr: &[Vec<f64>]) -> f64 {
+    let mut z = sr[x][u];
+    for _ in 0..x {
+        z *= u as f64 / n as f64;
+    }
+    for v in 1..=u {
+        z *= (n - v + 1) as f64 / (u - v + 1) as f64;
+    }
+    z
+}
+
+/// Compute the probability mass function of the number of distinct elements selected in
+/// <code>x</code> random draws with replacement from a set of size <code>n</code>, i.e. the
+/// vector of probabilities of selecting exactly <code>j</code> distinct elements, for
+/// <code>j = 0..=min(x,n)</code>.  These sum to approximately one.
+/// <br>&nbsp;
+///
+/// <b>Method.</b>  The probability for <code>j</code> is
+/// <code>SR(x,j) * (j/n)^x * choose(n,j)</code>, as for
+/// <code>p_at_most_m_distinct_in_sample_of_x_from_n</code>, using a precomputed Stirling
+/// ratio table, which must have at least <code>x + 1</code> rows.
+/// <br><br>
+/// <b>Complexity.  </b> <code>O( min(x,n) * x )</code>.
+/// <br><br>
+/// <b>Testing.</b> We check that tail sums agree with
+/// <code>p_at_most_m_distinct_in_sample_of_x_from_n</code>.
+
+pub fn distinct_count_pmf(x: usize, n: usize, sr: &[Vec<f64>]) -> Vec<f64> {
+    (0..=x.min(n))
+        .map(|j| p_exactly_u_distinct(j, x, n, sr))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
 
+    // Test stirling2.  Works with "cargo test".
+
+    #[test]
+    fn test_stirling2() {
+        use num_bigint::BigUint;
+
+        use super::*;
+
+        assert_eq!(stirling2::<f64>(10, 5), 42525.0);
+        let n_max = 40;
+        let s = stirling2_table::<f64>(n_max);
+        let sbig = stirling2_table::<BigUint>(n_max);
+        for n in 0..=n_max {
+            for k in 0..=n + 1 {
+                let (x, xbig) = if k <= n {
+                    (s[n][k], sbig[n][k].clone())
+                } else {
+                    (0.0, BigUint::from(0_u32))
+                };
+                assert_eq!(stirling2::<f64>(n, k), x);
+                assert_eq!(stirling2::<BigUint>(n, k), xbig);
+            }
+        }
+    }
+
+    // Test distinct_count_pmf.  Works with "cargo test".
+
+    #[test]
+    fn test_distinct_count_pmf() {
+        use super::*;
+
+        let sr = stirling2_ratio_table::<f64>(100);
+        for (x, n) in [(0, 5), (5, 3), (30, 2500), (100, 40)] {
+            let pmf = distinct_count_pmf(x, n, &sr);
+            assert_eq!(pmf.len(), x.min(n) + 1);
+            assert!((pmf.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+            for m in 0..=x.min(n) {
+                let tail = pmf[m + 1..].iter().sum::<f64>();
+                let p = p_at_most_m_distinct_in_sample_of_x_from_n(m, x, n, &sr);
+                assert!((1.0 - tail - p).abs() < 1e-12);
+            }
+        }
+        assert_eq!(distinct_count_pmf(3, 1, &sr), [0.0, 1.0]);
+    }
+
+    // Test p_at_most_m_distinct_in_sample_of_x_from_n for a sample larger than n + 1, which
+    // used to overflow.  Works with "cargo test".
+
+    #[test]
+    fn test_p_at_most_m_distinct_large_sample() {
+        use super::*;
+
+        let sr = stirling2_ratio_table::<f64>(10);
+        let p = p_at_most_m_distinct_in_sample_of_x_from_n(1, 5, 2, &sr);
+        assert!((p - 1.0 / 16.0).abs() < 1e-12);
+        assert_eq!(
+            p_at_most_m_distinct_in_sample_of_x_from_n(2, 10, 2, &sr),
+            1.0
+        );
+    }
+
+    // Test bell_numbers.  Works with "cargo test".
+
+    #[test]
+    fn test_bell_numbers() {
+        use num_bigint::{BigUint, ToBigUint};
+
+        use super::*;
+
+        let exact = [1, 1, 2, 5, 15, 52, 203, 877, 4140, 21147, 115975, 678570];
+        let b = bell_numbers::<BigUint>(exact.len() - 1);
+        let bf = bell_numbers::<f64>(exact.len() - 1);
+        for n in 0..exact.len() {
+            assert_eq!(b[n], exact[n].to_biguint().unwrap());
+            assert_eq!(bf[n], exact[n] as f64);
+        }
+
+        // Check the accuracy claim for f64: values are exact for n ≤ 22, but not for n = 23.
+
+        let b = bell_numbers::<BigUint>(23);
+        let bf = bell_numbers::<f64>(23);
+        for n in 0..=22 {
+            assert_eq!(bf[n].to_biguint().unwrap(), b[n]);
+        }
+        assert_ne!(bf[23].to_biguint().unwrap(), b[23]);
+    }
+
+    // Test stirling1_table.  Works with "cargo test".
+
+    #[test]
+    fn test_stirling1_table() {
+        use num_bigint::{BigUint, ToBigUint};
+
+        use super::*;
+
+        // Test one value in stirling1_table<f64> versus value in wikipedia.
+
+        let c = stirling1_table::<f64>(10);
+        assert_eq!(c[10][5], 269325.0);
+
+        // Verify that each row of the exact table sums to n!.
+
+        let n_max = 100;
+        let cbig = stirling1_table::<BigUint>(n_max);
+        let mut nf = 1.to_biguint().unwrap();
+        for (n, row) in cbig.iter().enumerate() {
+            if n > 0 {
+                nf *= n.to_biguint().unwrap();
+            }
+            let sum = row.iter().fold(0.to_biguint().unwrap(), |a, x| a + x);
+            assert_eq!(sum, nf);
+        }
+    }
+
     // Test stirling stuff.  Works with "cargo test".
 
     #[test]
@@ -3,6 +3,7 @@
 // This file contains some miscellaneous string utilities.
 
 use std::cmp::max;
+use std::collections::HashMap;
 use std::fmt::Write;
 use vector_utils::next_diff;
 
@@ -171,6 +172,156 @@ pub fn parse_csv(x: &str) -> Vec<String> {
     y
 }
 
+// Find the longest common prefix or suffix of a list of strings.  These are computed at
+// character boundaries, so the result is always a valid string.  For empty input, return
+// the empty string.
+
+pub fn common_prefix<'a>(strings: &[&'a str]) -> &'a str {
+    if strings.is_empty() {
+        return "";
+    }
+    let mut p = strings[0];
+    for s in strings[1..].iter() {
+        let mut n = 0;
+        for (c, d) in p.chars().zip(s.chars()) {
+            if c != d {
+                break;
+            }
+            n += c.len_utf8();
+        }
+        p = &p[0..n];
+    }
+    p
+}
+
+pub fn common_suffix<'a>(strings: &[&'a str]) -> &'a str {
+    if strings.is_empty() {
+        return "";
+    }
+    let mut p = strings[0];
+    for s in strings[1..].iter() {
+        let mut n = 0;
+        for (c, d) in p.chars().rev().zip(s.chars().rev()) {
+            if c != d {
+                break;
+            }
+            n += c.len_utf8();
+        }
+        p = &p[p.len() - n..];
+    }
+    p
+}
+
+// Convert a string into something that can safely be used as a file name.  Each character
+// in the following class is replaced by an underscore:
+// • a path separator: / or \
+// • an ASCII control character (0x00-0x1f or 0x7f), or whitespace (including unicode whitespace)
+// • one of : * ? " < > |, which are not allowed in file names on Windows.
+// All other characters, including non-ASCII letters, are kept.  Runs of underscores are then
+// collapsed to a single underscore, and the result is truncated (at a character boundary) to
+// at most SANITIZE_FILENAME_MAX_BYTES bytes, the file name length limit on most filesystems.
+// So that the name is also usable on Windows, trailing dots (which Windows drops) are removed,
+// and if the part before the first dot is a reserved device name (CON, PRN, AUX, NUL, COM1-COM9
+// or LPT1-LPT9, in any case), an underscore is prepended.  Finally, an empty result (including
+// what is left of "." or "..") is replaced by "_".
+
+pub const SANITIZE_FILENAME_MAX_BYTES: usize = 255;
+
+pub fn sanitize_filename(s: &str) -> String {
+    let mut t = String::with_capacity(s.len());
+    for c in s.chars() {
+        let bad = c.is_control()
+            || c.is_whitespace()
+            || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|');
+        let c = if bad { '_' } else { c };
+        if c != '_' || !t.ends_with('_') {
+            if t.len() + c.len_utf8() > SANITIZE_FILENAME_MAX_BYTES {
+                break;
+            }
+            t.push(c);
+        }
+    }
+    while t.ends_with('.') {
+        t.pop();
+    }
+    let stem = t.split('.').next().unwrap().to_ascii_uppercase();
+    let reserved = match stem.as_bytes() {
+        b"CON" | b"PRN" | b"AUX" | b"NUL" => true,
+        [b'C', b'O', b'M', d] | [b'L', b'P', b'T', d] => (b'1'..=b'9').contains(d),
+        _ => false,
+    };
+    if reserved {
+        t.insert(0, '_');
+        while t.len() > SANITIZE_FILENAME_MAX_BYTES {
+            t.pop();
+        }
+    }
+    if t.is_empty() {
+        t = "_".to_string();
+    }
+    t
+}
+
+// Fill in a template, replacing each placeholder {name} by the value of name in vars.  Use {{
+// and }} to get literal braces.  A placeholder whose name is not in vars, or a { that is not
+// closed, is left as is by interpolate, whereas interpolate_strict returns an error for these.
+
+pub fn interpolate(template: &str, vars: &HashMap<String, String>) -> String {
+    interpolate_core(template, vars, false).unwrap()
+}
+
+pub fn interpolate_strict(
+    template: &str,
+    vars: &HashMap<String, String>,
+) -> Result<String, String> {
+    interpolate_core(template, vars, true)
+}
+
+fn interpolate_core(
+    template: &str,
+    vars: &HashMap<String, String>,
+    strict: bool,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(p) = rest.find(['{', '}']) {
+        out.push_str(&rest[..p]);
+        let t = &rest[p..];
+        if t.starts_with("{{") || t.starts_with("}}") {
+            out.push_str(&t[..1]);
+            rest = &t[2..];
+        } else if let Some(after) = t.strip_prefix('}') {
+            out.push('}');
+            rest = after;
+        } else {
+            let after = &t[1..];
+            match after.find(['{', '}']) {
+                Some(q) if after.as_bytes()[q] == b'}' => {
+                    let name = &after[..q];
+                    match vars.get(name) {
+                        Some(value) => out.push_str(value),
+                        None if strict => return Err(format!("unknown placeholder {{{}}}", name)),
+                        None => out.push_str(&t[..q + 2]),
+                    }
+                    rest = &after[q + 1..];
+                }
+                _ => {
+                    if strict {
+                        return Err(format!(
+                            "unclosed {{ at position {}",
+                            template.len() - t.len()
+                        ));
+                    }
+                    out.push('{');
+                    rest = after;
+                }
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
 // Quote a bunch of strings.
 
 pub fn quote_vec(x: &[&str]) -> Vec<String> {
@@ -226,6 +377,37 @@ pub fn add_commas(n: usize) -> String {
     result
 }
 
+// Format a duration, given in seconds (e.g. as returned by perf_stats::elapsed), rounded to the
+// nearest second.  format_duration gives e.g. "1h 23m 45s", omitting leading zero units, so that
+// e.g. 45 seconds is "45s", and zero is "0s".  format_duration_short gives e.g. "1:23:45", or
+// "23:45" if the duration is less than an hour.
+
+fn duration_hms(seconds: f64) -> (u64, u64, u64) {
+    assert!(seconds >= 0.0, "negative duration {}", seconds);
+    let s = seconds.round() as u64;
+    (s / 3600, (s / 60) % 60, s % 60)
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let (h, m, s) = duration_hms(seconds);
+    if h > 0 {
+        format!("{}h {}m {}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+pub fn format_duration_short(seconds: f64) -> String {
+    let (h, m, s) = duration_hms(seconds);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
+}
+
 // decimal_diffs: given two strings, determine if they are identical except for
 // numerical differences, as e.g.
 // woof_1.2x_3
@@ -325,3 +507,96 @@ pub fn hcat(col1: &[String], col2: &[String], sep: usize) -> Vec<String> {
     }
     cat
 }
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p string_utils
+
+    use crate::{
+        common_prefix, common_suffix, format_duration, format_duration_short, interpolate,
+        interpolate_strict, sanitize_filename, SANITIZE_FILENAME_MAX_BYTES,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_common_prefix_suffix() {
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(common_suffix(&[]), "");
+        assert_eq!(common_prefix(&["woof"]), "woof");
+        assert_eq!(common_suffix(&["woof"]), "woof");
+        assert_eq!(common_prefix(&["woof", "woof"]), "woof");
+        assert_eq!(common_suffix(&["woof", "woof"]), "woof");
+        assert_eq!(common_prefix(&["abc", "xyz"]), "");
+        assert_eq!(common_suffix(&["abc", "xyz"]), "");
+        let names = ["sample_α1_rep", "sample_α2_rep", "sample_αβ_rep"];
+        assert_eq!(common_prefix(&names), "sample_α");
+        assert_eq!(common_suffix(&names), "_rep");
+        assert_eq!(common_prefix(&["é", "è"]), "");
+    }
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("a/b\\c"), "a_b_c");
+        assert_eq!(sanitize_filename("sample 1 / day  2"), "sample_1_day_2");
+        assert_eq!(sanitize_filename("x__y\t\nz"), "x_y_z");
+        assert_eq!(
+            sanitize_filename("Müller–Zoë 東京.txt"),
+            "Müller–Zoë_東京.txt"
+        );
+        assert_eq!(sanitize_filename("/\\:*?\"<>| \u{0}\u{7f}"), "_");
+        assert_eq!(sanitize_filename(""), "_");
+        assert_eq!(sanitize_filename(".."), "_");
+        let long = "é".repeat(200);
+        let t = sanitize_filename(&long);
+        assert_eq!(t.len(), 254);
+        assert!(long.starts_with(&t));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0.0), "0s");
+        assert_eq!(format_duration_short(0.0), "0:00");
+        assert_eq!(format_duration(44.6), "45s");
+        assert_eq!(format_duration_short(44.6), "0:45");
+        assert_eq!(format_duration(125.0), "2m 5s");
+        assert_eq!(format_duration(5025.0), "1h 23m 45s");
+        assert_eq!(format_duration_short(5025.0), "1:23:45");
+        assert_eq!(format_duration(36000.0), "10h 0m 0s");
+        assert_eq!(format_duration_short(36005.0), "10:00:05");
+    }
+
+    #[test]
+    fn test_sanitize_filename_windows() {
+        assert_eq!(sanitize_filename("con"), "_con");
+        assert_eq!(sanitize_filename("LPT3.txt"), "_LPT3.txt");
+        assert_eq!(sanitize_filename("COM0"), "COM0");
+        assert_eq!(sanitize_filename("console"), "console");
+        assert_eq!(sanitize_filename("report..."), "report");
+        assert_eq!(sanitize_filename(". ."), "._");
+        let bad = (0..32_u8).map(|c| c as char).collect::<String>() + "/\\:*?\"<>|\u{7f}";
+        assert_eq!(sanitize_filename(&bad), "_");
+        let long = format!("nul.{}", "x".repeat(300));
+        assert_eq!(sanitize_filename(&long).len(), SANITIZE_FILENAME_MAX_BYTES);
+        assert!(sanitize_filename(&long).starts_with("_nul.x"));
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let mut vars = HashMap::<String, String>::new();
+        vars.insert("sample".to_string(), "pbmc".to_string());
+        vars.insert("n".to_string(), "1000".to_string());
+        let t = "{sample} has {n} cells";
+        assert_eq!(interpolate(t, &vars), "pbmc has 1000 cells");
+        assert_eq!(interpolate_strict(t, &vars).unwrap(), "pbmc has 1000 cells");
+        let t = "{sample}: {missing} {n}";
+        assert_eq!(interpolate(t, &vars), "pbmc: {missing} 1000");
+        assert!(interpolate_strict(t, &vars).is_err());
+        let t = "{{sample}} = {sample}, {{}} and }}{{";
+        assert_eq!(interpolate(t, &vars), "{sample} = pbmc, {} and }{");
+        assert_eq!(interpolate("a { b {n}", &vars), "a { b 1000");
+        assert!(interpolate_strict("a { b {n}", &vars).is_err());
+        assert_eq!(interpolate("", &vars), "");
+    }
+}
@@ -1,11 +1,15 @@
 // Copyright (c) 2018 10x Genomics, Inc. All rights reserved.
 
-// Functions print_tabular and print_tabular_vbox for making pretty tables.  And related utilities.
+// Functions print_tabular, print_tabular_vbox and print_tabular_markdown for making pretty tables,
+// write_tabular_vbox for writing boxed tables directly, and print_tabular_csv and
+// write_tabular_csv for writing them in CSV format.  And related utilities.
 
-use io_utils::eprintme;
 use itertools::Itertools;
 use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::io::Write;
 use string_utils::strme;
+use unicode_width::UnicodeWidthChar;
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
@@ -59,7 +63,8 @@ pub fn package_characters_with_escapes_char(c: &[char]) -> Vec<Vec<char>> {
 
 // Print out a matrix, with left-justified entries, and given separation between
 // columns.  (Justification may be changed by supplying an optional argument
-// consisting of a string of l's and r's.)
+// consisting of a string of l's, r's and c's, the latter meaning centered, with any extra
+// space going on the right.)
 
 pub fn print_tabular(
     log: &mut Vec<u8>,
@@ -67,45 +72,70 @@ pub fn print_tabular(
     sep: usize,
     justify: Option<Vec<u8>>,
 ) {
+    print_tabular_with_widths(log, rows, sep, justify, &[]);
+}
+
+// Same as print_tabular, but make each column j at least min_widths[j] wide, and return the
+// column widths.  This allows several tables to be aligned with each other: print each of them
+// (to a scratch log) to find their widths, take the max of these, and then print each of them
+// using that as min_widths.
+
+pub fn print_tabular_with_widths(
+    log: &mut Vec<u8>,
+    rows: &[Vec<String>],
+    sep: usize,
+    justify: Option<Vec<u8>>,
+    min_widths: &[usize],
+) -> Vec<usize> {
     let just = match justify {
         Some(x) => x,
         None => Vec::<u8>::new(),
     };
     let nrows = rows.len();
-    let mut ncols = 0;
+    let mut ncols = min_widths.len();
     for i in 0..nrows {
         ncols = max(ncols, rows[i].len());
     }
-    let mut maxcol = vec![0; ncols];
+    let mut maxcol = min_widths.to_vec();
+    maxcol.resize(ncols, 0);
     for i in 0..rows.len() {
         for j in 0..rows[i].len() {
-            maxcol[j] = max(maxcol[j], rows[i][j].chars().count());
+            maxcol[j] = max(maxcol[j], visible_width(&rows[i][j]));
         }
     }
     for i in 0..rows.len() {
         for j in 0..rows[i].len() {
             let x = rows[i][j].clone();
             if j < just.len() && just[j] == b'r' {
-                log.append(&mut vec![b' '; maxcol[j] - x.chars().count()]);
+                log.append(&mut vec![b' '; maxcol[j] - visible_width(&x)]);
                 log.append(&mut x.as_bytes().to_vec());
                 if j < rows[i].len() - 1 {
                     log.append(&mut vec![b' '; sep]);
                 }
+            } else if j < just.len() && just[j] == b'c' {
+                let pad = maxcol[j] - visible_width(&x);
+                log.append(&mut vec![b' '; pad / 2]);
+                log.append(&mut x.as_bytes().to_vec());
+                if j < rows[i].len() - 1 {
+                    log.append(&mut vec![b' '; pad - pad / 2 + sep]);
+                }
             } else {
                 log.append(&mut x.as_bytes().to_vec());
                 if j < rows[i].len() - 1 {
-                    log.append(&mut vec![b' '; maxcol[j] - x.chars().count() + sep]);
+                    log.append(&mut vec![b' '; maxcol[j] - visible_width(&x) + sep]);
                 }
             }
         }
         log.push(b'\n');
     }
+    maxcol
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
-// Compute the visible length of a string, counting unicode characters as width one and
-// ignoring some ASCII escape sequences.
+// Compute the visible length of a string, i.e. the number of terminal columns that it occupies,
+// ignoring some ASCII escape sequences.  Wide characters, such as CJK ideographs and many emoji,
+// have width two, while control characters and combining characters have width zero.
 
 pub fn visible_width(s: &str) -> usize {
     let mut n = 0;
@@ -117,14 +147,72 @@ pub fn visible_width(s: &str) -> usize {
         } else if escaped && c == 'm' {
             escaped = false;
         } else {
-            n += 1;
+            n += c.width().unwrap_or(0);
         }
     }
     n
 }
 
+// Remove the escape sequences recognized by visible_width from a string.
+
+pub fn strip_ansi(s: &str) -> String {
+    let mut t = String::with_capacity(s.len());
+    let mut escaped = false;
+    for c in s.chars() {
+        if escaped && c != 'm' {
+        } else if c == '\u{1b}' {
+            escaped = true;
+        } else if escaped && c == 'm' {
+            escaped = false;
+        } else {
+            t.push(c);
+        }
+    }
+    t
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
+// Escape sequences used for shading by print_tabular_vbox: a light grey background (color 254
+// of the 256-color palette), and reset.
+
+const SHADE_START: &str = "\u{1b}[48;5;254m";
+const SHADE_END: &str = "\u{1b}[0m";
+
+// BoxStyle: the style of the box characters used by print_tabular_vbox.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoxStyle {
+    Light,
+    Heavy,
+    Rounded,
+    Double,
+}
+
+impl BoxStyle {
+    // Return the box characters: horizontal and vertical lines, the four corners (top left, top
+    // right, bottom left, bottom right), and the junctions tee, up tee, cross, left and right.
+
+    fn chars(self) -> [char; 11] {
+        match self {
+            BoxStyle::Light => ['─', '│', '┌', '┐', '└', '┘', '┬', '┴', '┼', '├', '┤'],
+            BoxStyle::Heavy => ['━', '┃', '┏', '┓', '┗', '┛', '┳', '┻', '╋', '┣', '┫'],
+            BoxStyle::Rounded => ['─', '│', '╭', '╮', '╰', '╯', '┬', '┴', '┼', '├', '┤'],
+            BoxStyle::Double => ['═', '║', '╔', '╗', '╚', '╝', '╦', '╩', '╬', '╠', '╣'],
+        }
+    }
+}
+
+impl From<bool> for BoxStyle {
+    fn from(bold_box: bool) -> Self {
+        if bold_box {
+            BoxStyle::Heavy
+        } else {
+            BoxStyle::Light
+        }
+    }
+}
+
 // Print out a matrix, with given separation between columns.  Rows of the matrix
 // may contain arbitrary UTF-8 and some escape sequences.  Put the entire thing in a box, with
 // extra vertical bars.  The argument justify consists of symbols l and r, denoting
@@ -136,7 +224,9 @@ pub fn visible_width(s: &str) -> usize {
 // By a "matrix entry", we mean one of the Strings in "rows".
 //
 // Entries that begin with a backslash are reserved for future features.
-// Symbols other than l or r or | in "justify" are reserved for future features.
+// The symbols l, r and c in "justify" mean left, right and center justification, and |
+// means a vertical bar.  Other symbols are reserved for future features.  When an entry is
+// centered and the padding cannot be split evenly, the extra space goes to the right.
 //
 // An entry may be followed on the right by one more entries whose contents are
 // exactly "\ext".  In that case the entries are treated as multi-column.  Padding
@@ -146,7 +236,17 @@ pub fn visible_width(s: &str) -> usize {
 // use one or more of these in succession horizontally to connect two vertical lines.  Cannot
 // be combined with \ext.
 //
-// bold_box: use bold box characters
+// An entry may contain newlines, in which case it is displayed on several lines, and the other
+// entries in its row are padded with blank lines.  To limit the width of a table, long entries
+// may be broken into lines first, using wrap_entries.
+//
+// style: the BoxStyle, i.e. which box characters to use; for compatibility, this may also be a
+// bool, with true meaning BoxStyle::Heavy (bold) and false meaning BoxStyle::Light
+//
+// shade_alternate: give every other data row a light grey background, starting with the second
+// one.  Here data rows are those that do not contain \hline, and a row that is displayed on
+// several lines counts once.  The box characters are not
+// shaded.  This is done using escape sequences, which do not affect the layout.
 //
 // Really only guaranteed to work for the tested cases.
 
@@ -156,41 +256,189 @@ pub fn print_tabular_vbox(
     sep: usize,
     justify: &[u8],
     debug_print: bool,
-    bold_box: bool,
+    style: impl Into<BoxStyle>,
+    shade_alternate: bool,
 ) {
+    try_print_tabular_vbox(log, rows, sep, justify, debug_print, style, shade_alternate)
+        .unwrap_or_else(|e| panic!("{}", e));
+}
+
+// Same as print_tabular_vbox, but return an error if justify is malformed, i.e. the number of
+// l, r or c symbols in it is not the number of columns, or a | is not between two of them.
+// Nothing is printed in that case.
+
+pub fn try_print_tabular_vbox(
+    log: &mut String,
+    rows: &[Vec<String>],
+    sep: usize,
+    justify: &[u8],
+    debug_print: bool,
+    style: impl Into<BoxStyle>,
+    shade_alternate: bool,
+) -> Result<(), String> {
+    try_print_tabular_vbox_seps(
+        log,
+        rows,
+        &[sep],
+        justify,
+        debug_print,
+        style,
+        shade_alternate,
+    )
+}
+
+// Same as print_tabular_vbox, but with the separation specified separately for each gap between
+// columns: seps[j] is the separation after column j, so there should be one fewer of these than
+// there are columns.  If a vertical bar follows column j, then seps[j] is used on both sides of
+// it.  If seps has length one, it is used for all gaps, as in print_tabular_vbox.
+
+pub fn print_tabular_vbox_seps(
+    log: &mut String,
+    rows: &[Vec<String>],
+    seps: &[usize],
+    justify: &[u8],
+    debug_print: bool,
+    style: impl Into<BoxStyle>,
+    shade_alternate: bool,
+) {
+    try_print_tabular_vbox_seps(
+        log,
+        rows,
+        seps,
+        justify,
+        debug_print,
+        style,
+        shade_alternate,
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+}
+
+// Same as print_tabular_vbox_seps, but return an error if justify or seps is malformed.
+
+pub fn try_print_tabular_vbox_seps(
+    log: &mut String,
+    rows: &[Vec<String>],
+    seps: &[usize],
+    justify: &[u8],
+    debug_print: bool,
+    style: impl Into<BoxStyle>,
+    shade_alternate: bool,
+) -> Result<(), String> {
+    let mut out = Vec::<u8>::new();
+    write_tabular_vbox_core(
+        &mut out,
+        rows,
+        seps,
+        justify,
+        debug_print,
+        style.into(),
+        shade_alternate,
+    )?;
+    log.push_str(strme(&out));
+    Ok(())
+}
+
+// Same as try_print_tabular_vbox, but write the table to out, one line at a time, rather than
+// appending it to a String.  This avoids holding the entire table in memory, so is much more
+// efficient for very large tables.  Nothing is written if justify is malformed.  An error is
+// also returned if writing fails.
+
+pub fn write_tabular_vbox<W: Write>(
+    out: &mut W,
+    rows: &[Vec<String>],
+    sep: usize,
+    justify: &[u8],
+    style: impl Into<BoxStyle>,
+    shade_alternate: bool,
+) -> Result<(), String> {
+    write_tabular_vbox_core(
+        out,
+        rows,
+        &[sep],
+        justify,
+        false,
+        style.into(),
+        shade_alternate,
+    )
+}
+
+// Split the entries of a row into lines.  The row is displayed as the given number of lines,
+// with line k consisting of line k of each entry, or empty if there is no such line.  The
+// entries \ext and \hline are repeated on every line.
+
+fn split_row(row: &[String]) -> (Vec<Vec<&str>>, usize) {
+    let mut lines = Vec::<Vec<&str>>::with_capacity(row.len());
+    for x in row.iter() {
+        if *x == "\\ext" || *x == "\\hline" {
+            lines.push(vec![x.as_str()]);
+        } else {
+            lines.push(x.split('\n').collect());
+        }
+    }
+    let height = row.iter().map(|x| x.split('\n').count()).max().unwrap_or(1);
+    (lines, height)
+}
+
+fn row_line<'a>(lines: &[Vec<&'a str>], k: usize) -> Vec<&'a str> {
+    let mut r = Vec::<&str>::with_capacity(lines.len());
+    for x in lines.iter() {
+        if x.len() == 1 && (x[0] == "\\ext" || x[0] == "\\hline") {
+            r.push(x[0]);
+        } else {
+            r.push(x.get(k).copied().unwrap_or(""));
+        }
+    }
+    r
+}
+
+fn write_tabular_vbox_core<W: Write>(
+    out: &mut W,
+    rows: &[Vec<String>],
+    seps: &[usize],
+    justify: &[u8],
+    debug_print: bool,
+    style: BoxStyle,
+    shade_alternate: bool,
+) -> Result<(), String> {
     // Define box characters.
 
-    let dash = if !bold_box { '─' } else { '━' };
-    let verty = if !bold_box { '│' } else { '┃' };
-    let topleft = if !bold_box { '┌' } else { '┏' };
-    let topright = if !bold_box { '┐' } else { '┓' };
-    let botleft = if !bold_box { '└' } else { '┗' };
-    let botright = if !bold_box { '┘' } else { '┛' };
-    let tee = if !bold_box { '┬' } else { '┳' };
-    let uptee = if !bold_box { '┴' } else { '┻' };
-    let cross = if !bold_box { '┼' } else { '╋' };
-    let lefty = if !bold_box { '├' } else { '┣' };
-    let righty = if !bold_box { '┤' } else { '┫' };
-
-    // Proceed.
-
-    let mut rrr = rows.to_owned();
-    let nrows = rrr.len();
+    let [dash, verty, topleft, topright, botleft, botright, tee, uptee, cross, lefty, righty] =
+        style.chars();
+
+    // Check the arguments.
+
     let mut ncols = 0;
-    for i in 0..nrows {
-        ncols = max(ncols, rrr[i].len());
+    for i in 0..rows.len() {
+        ncols = max(ncols, rows[i].len());
+    }
+    let mut sep = vec![0; ncols];
+    if seps.len() == 1 {
+        sep = vec![seps[0]; ncols];
+    } else if seps.len() + 1 != ncols {
+        return Err(format!(
+            "\nError.  Your table has {} columns, so seps should have {} entries, \
+             but it has {}.",
+            ncols,
+            ncols.saturating_sub(1),
+            seps.len()
+        ));
+    } else {
+        sep[0..ncols - 1].copy_from_slice(seps);
     }
     let mut vert = vec![false; ncols];
     let mut just = Vec::<u8>::new();
     let mut count = 0_isize;
     for i in 0..justify.len() {
         if justify[i] == b'|' {
-            assert!(count > 0);
-            if count >= ncols as isize {
-                eprintln!("\nposition of | in justify string is illegal");
-                eprintme!(count, ncols);
+            if count == 0 || count >= ncols as isize {
+                return Err(format!(
+                    "\nError.  The position of | in justify string {} is illegal.  It is \
+                     after {} of the {} columns, but should be between two of them.",
+                    strme(justify),
+                    count,
+                    ncols
+                ));
             }
-            assert!(count < ncols as isize);
             vert[(count - 1) as usize] = true;
         } else {
             just.push(justify[i]);
@@ -198,326 +446,652 @@ pub fn print_tabular_vbox(
         }
     }
     if just.len() != ncols {
-        eprintln!(
+        let mut msg = format!(
             "\nError.  Your table has {} columns but the number of \
-             l or r symbols in justify is {}.\nThese numbers should be equal.",
+             l, r or c symbols in justify is {}.\nThese numbers should be equal.\n",
             ncols,
             just.len()
         );
-        eprintln!("justify = {}", strme(justify));
+        msg += &format!("justify = {}", strme(justify));
         for i in 0..rows.len() {
-            eprintln!(
-                "row {} = {} = {}",
+            msg += &format!(
+                "\nrow {} = {} = {}",
                 i + 1,
                 rows[i].len(),
                 rows[i].iter().format(",")
             );
         }
-        assert_eq!(just.len(), ncols);
+        return Err(msg);
     }
+
+    // Find the column widths.  Here and below, entries that contain newlines are split into
+    // lines, and each line is treated as a row, which we call a line row.  For each column,
+    // colmax is the maximum width of its entries other than \ext, which is used below.
+
     let mut maxcol = vec![0; ncols];
-    let mut ext = vec![0; ncols];
-    for i in 0..rrr.len() {
-        for j in 0..rrr[i].len() {
-            if j < rrr[i].len() - 1 && rrr[i][j + 1] == *"\\ext" {
-                continue;
-            }
-            if rrr[i][j] == *"\\ext" || rrr[i][j] == *"\\hline" {
-                continue;
+    let mut colmax = vec![0; ncols];
+    for row in rows.iter() {
+        let (lines, height) = split_row(row);
+        for k in 0..height {
+            let r = row_line(&lines, k);
+            for j in 0..r.len() {
+                if r[j] != "\\ext" {
+                    colmax[j] = max(colmax[j], visible_width(r[j]));
+                }
+                if j < r.len() - 1 && r[j + 1] == "\\ext" {
+                    continue;
+                }
+                if r[j] == "\\ext" || r[j] == "\\hline" {
+                    continue;
+                }
+                maxcol[j] = max(maxcol[j], visible_width(r[j]));
             }
-            maxcol[j] = max(maxcol[j], visible_width(&rrr[i][j]));
         }
     }
     if debug_print {
         println!("maxcol = {}", maxcol.iter().format(","));
     }
 
-    // Add space according to ext entries.
+    // Add space according to ext entries.  The number of spaces to be added to the right of
+    // entry j of line row i is pad[(i, j)].  An entry is padded on the right so that it is at
+    // least as wide as the entries above it in its column.
 
-    for i in 0..rrr.len() {
-        for j in 0..rrr[i].len() {
-            if j < rrr[i].len() - 1 && rrr[i][j + 1] == *"\\ext" && rrr[i][j] != *"\\ext" {
-                let mut k = j + 1;
-                while k < rrr[i].len() {
-                    if rrr[i][k] != *"\\ext" {
-                        break;
+    let mut ext = vec![0; ncols];
+    let mut pad = HashMap::<(usize, usize), usize>::new();
+    let mut i = 0;
+    for row in rows.iter() {
+        let (lines, height) = split_row(row);
+        for k in 0..height {
+            let r = row_line(&lines, k);
+            for j in 0..r.len() {
+                if j < r.len() - 1 && r[j + 1] == "\\ext" && r[j] != "\\ext" {
+                    let mut k = j + 1;
+                    while k < r.len() {
+                        if r[k] != "\\ext" {
+                            break;
+                        }
+                        k += 1;
                     }
-                    k += 1;
-                }
-                let need = visible_width(&rrr[i][j]);
-                let mut have = 0;
-                for l in j..k {
-                    have += maxcol[l];
-                    if l < k - 1 {
-                        have += sep;
-                        if vert[l] {
-                            have += sep + 1;
+                    let need = visible_width(r[j]);
+                    let mut have = 0;
+                    for l in j..k {
+                        have += maxcol[l];
+                        if l < k - 1 {
+                            have += sep[l];
+                            if vert[l] {
+                                have += sep[l] + 1;
+                            }
                         }
                     }
-                }
-                if debug_print {
-                    println!("row {} column {}, have = {}, need = {}", i, j, have, need);
-                }
-                if have > need {
                     if debug_print {
-                        println!(
-                            "adding {} spaces to right of row {} col {}",
-                            have - need,
-                            i,
-                            j
-                        );
+                        println!("row {} column {}, have = {}, need = {}", i, j, have, need);
                     }
-                    for _ in need..have {
-                        rrr[i][j].push(' ');
+                    let mut width = need;
+                    if have > need {
+                        if debug_print {
+                            println!(
+                                "adding {} spaces to right of row {} col {}",
+                                have - need,
+                                i,
+                                j
+                            );
+                        }
+                        width = have;
+                    } else if need > have {
+                        maxcol[k - 1] += need - have;
+                        if debug_print {
+                            println!("increasing maxcol[{}] to {}", k - 1, maxcol[k - 1]);
+                        }
+                        ext[k - 1] += need - have;
                     }
-                } else if need > have {
-                    maxcol[k - 1] += need - have;
-                    if debug_print {
-                        println!("increasing maxcol[{}] to {}", k - 1, maxcol[k - 1]);
+                    colmax[j] = max(colmax[j], width);
+                    width = colmax[j];
+                    if width > need {
+                        pad.insert((i, j), width - need);
                     }
-                    ext[k - 1] += need - have;
                 }
-                let mut m = 0;
-                for u in 0..rrr.len() {
-                    if j >= rrr[u].len() {
-                        eprintln!("\nProblem with line {}, not enough fields.\n", u);
-                    }
-                    if rrr[u][j] != *"\\ext" {
-                        m = max(m, visible_width(&rrr[u][j]));
-                    }
+            }
+            i += 1;
+        }
+    }
+
+    // Form the lines of the table, one at a time, and output them.  Before a line is written,
+    // the edges of hlines in it are smoothed, which requires knowing the line before it (which
+    // has already been smoothed) and the line after it.  Therefore we hold two lines, the
+    // previous one and the current one, and process the current one when the next one comes in.
+    // Each line is represented as a vector of super-characters: a rust character, together with
+    // the escape code characters that came before it.  It is accompanied by a flag that says if
+    // it is to be shaded.
+
+    let box_chars = [verty, cross, lefty, righty, tee, uptee];
+
+    // Characters that continue a vertical line downward.  The previous line has already been
+    // smoothed, so a vertical line in it may appear as any of these, not just verty.
+    let vertical_below = [verty, tee, cross, lefty, righty];
+    let mut prev: Option<Vec<Vec<char>>> = None;
+    let mut cur: Option<(Vec<Vec<char>>, bool)> = None;
+    let to_chars = |s: &str| package_characters_with_escapes_char(&s.chars().collect::<Vec<_>>());
+    let mut process = |next: Option<(Vec<Vec<char>>, bool)>| -> Result<(), String> {
+        if let Some((mut line, shade)) = cur.take() {
+            // "Smooth" edges of hlines.
+
+            let below = next.as_ref().map(|x| &x.0);
+            for j in 0..line.len() {
+                let joins = j > 0
+                    && line[j - 1] == [dash]
+                    && line[j] == [verty]
+                    && j + 1 < line.len()
+                    && line[j + 1] == [dash];
+                if joins && matches!(below, Some(x) if j < x.len() && x[j] != [verty]) {
+                    line[j] = vec![uptee];
+                } else if joins
+                    && matches!(&prev, Some(x) if j < x.len()
+                        && !vertical_below.iter().any(|&c| x[j] == [c]))
+                {
+                    line[j] = vec![tee];
+                } else if joins {
+                    line[j] = vec![cross];
+                } else if line[j] == [verty] && j + 1 < line.len() && line[j + 1] == [dash] {
+                    line[j] = vec![lefty];
+                } else if j > 0
+                    && line[j - 1] == [dash]
+                    && line[j] == [verty]
+                    && (j + 1 == line.len() || line[j + 1] != [dash])
+                {
+                    line[j] = vec![righty];
                 }
-                if m > visible_width(&rrr[i][j]) {
-                    for _ in visible_width(&rrr[i][j])..m {
-                        rrr[i][j].push(' ');
+            }
+
+            // Output the line.  If it is shaded, the shading is turned on before each character
+            // that is not a box character (and again after any escape sequence that comes
+            // before it, since that might turn it off), and turned off before each box
+            // character.
+
+            let mut s = String::new();
+            let mut shading = false;
+            for x in line.iter() {
+                let n = x.len();
+                let c = x[n - 1];
+                if shade && box_chars.contains(&c) {
+                    if shading {
+                        s.push_str(SHADE_END);
+                        shading = false;
                     }
+                    s.push(c);
+                    continue;
+                }
+                for k in 0..n - 1 {
+                    s.push(x[k]);
                 }
+                if shade && (!shading || n > 1) {
+                    s.push_str(SHADE_START);
+                    shading = true;
+                }
+                s.push(c);
+            }
+            if shading {
+                s.push_str(SHADE_END);
             }
+            s.push('\n');
+            out.write_all(s.as_bytes())
+                .map_err(|e| format!("\nError.  Failed to write table: {}.", e))?;
+            prev = Some(line);
         }
-    }
+        cur = next;
+        Ok(())
+    };
 
     // Create top boundary of table.
 
+    let mut log = String::new();
     log.push(topleft);
     for i in 0..ncols {
         let mut n = maxcol[i];
         if i < ncols - 1 {
-            n += sep;
+            n += sep[i];
         }
         for _ in 0..n {
             log.push(dash);
         }
         if vert[i] {
-            log.push(tee);
-            for _ in 0..sep {
+            if rows[0][i + 1] != "\\ext" {
+                log.push(tee);
+            } else {
+                log.push(dash);
+            }
+            for _ in 0..sep[i] {
                 log.push(dash);
             }
         }
     }
     log.push(topright);
-    log.push('\n');
+    process(Some((to_chars(&log), false)))?;
 
-    // Go through the rows.
+    // Go through the rows.  Rows containing \hline are not shaded, and otherwise every other
+    // row is, starting with the second one.
 
-    for i in 0..nrows {
-        if debug_print {
-            println!("now row {} = {}", i, rrr[i].iter().format(","));
-            println!("0 - pushing │ onto row {}", i);
-        }
-        log.push(verty);
-        for j in 0..min(ncols, rrr[i].len()) {
-            // Pad entries according to justification.
-
-            let mut x = String::new();
-            if j >= rrr[i].len() {
-                for _ in 0..maxcol[j] {
-                    x.push(' ');
-                }
-            } else if rrr[i][j] == *"\\hline" {
-                for _ in 0..maxcol[j] {
-                    x.push(dash);
-                }
-            } else {
-                let r = rrr[i][j].clone();
-                let rlen = visible_width(&r);
-                let mut xlen = 0;
-                if r != *"\\ext" {
-                    if just[j] == b'r' {
-                        for _ in rlen..(maxcol[j] - ext[j]) {
+    let mut i = 0;
+    let mut data_rows = 0;
+    for row in rows.iter() {
+        let (lines, height) = split_row(row);
+        let mut shade = false;
+        if shade_alternate && !row.iter().any(|x| x == "\\hline") {
+            shade = data_rows % 2 == 1;
+            data_rows += 1;
+        }
+        for k in 0..height {
+            let r = row_line(&lines, k);
+            if debug_print {
+                println!("now row {} = {}", i, r.iter().format(","));
+                println!("0 - pushing │ onto row {}", i);
+            }
+            log.clear();
+            log.push(verty);
+            for j in 0..min(ncols, r.len()) {
+                // Pad entries according to justification.
+
+                let mut x = String::new();
+                if r[j] == "\\hline" {
+                    for _ in 0..maxcol[j] {
+                        x.push(dash);
+                    }
+                } else if r[j] != "\\ext" {
+                    let mut e = r[j].to_string();
+                    if let Some(&p) = pad.get(&(i, j)) {
+                        for _ in 0..p {
+                            e.push(' ');
+                        }
+                    }
+                    let elen = visible_width(&e);
+                    let mut xlen = 0;
+                    if just[j] == b'c' && elen < maxcol[j] - ext[j] {
+                        for _ in 0..(maxcol[j] - ext[j] - elen) / 2 {
                             x.push(' ');
                             xlen += 1;
                         }
                     }
-                    if j < rrr[i].len() {
-                        x += &r;
-                        xlen += visible_width(&r);
+                    if just[j] == b'r' {
+                        for _ in elen..(maxcol[j] - ext[j]) {
+                            x.push(' ');
+                            xlen += 1;
+                        }
                     }
+                    x += &e;
+                    xlen += elen;
                     if just[j] == b'r' {
                         for _ in (maxcol[j] - ext[j])..maxcol[j] {
                             x.push(' ');
                             xlen += 1;
                         }
                     }
-                    if just[j] == b'l' {
+                    if just[j] == b'l' || just[j] == b'c' {
                         for _ in xlen..maxcol[j] {
                             x.push(' ');
                         }
                     }
                 }
-            }
-            for c in x.chars() {
-                log.push(c);
-            }
+                log += &x;
 
-            // Add separations and separators.
+                // Add separations and separators.
 
-            let mut add_sep = true;
-            if j + 1 < rrr[i].len() && rrr[i][j + 1] == *"\\ext" {
-                add_sep = false;
-            }
-            let mut jp = j;
-            while jp + 1 < rrr[i].len() {
-                if rrr[i][jp + 1] != *"\\ext" {
-                    break;
+                let mut add_sep = true;
+                if j + 1 < r.len() && r[j + 1] == "\\ext" {
+                    add_sep = false;
                 }
-                jp += 1;
-            }
-            if add_sep && jp < ncols - 1 {
-                if rrr[i][j] == *"\\hline" {
-                    for _ in 0..sep {
-                        log.push(dash);
-                    }
-                } else {
-                    for _ in 0..sep {
-                        log.push(' ');
+                let mut jp = j;
+                while jp + 1 < r.len() {
+                    if r[jp + 1] != "\\ext" {
+                        break;
                     }
+                    jp += 1;
                 }
-            }
-            if vert[j] && rrr[i][j + 1] != "\\ext" {
-                if debug_print {
-                    println!("1 - pushing {} onto row {}, j = {}", verty, i, j);
+                if add_sep && jp < ncols - 1 {
+                    let c = if r[j] == "\\hline" { dash } else { ' ' };
+                    for _ in 0..sep[j] {
+                        log.push(c);
+                    }
                 }
-                log.push(verty);
-                if rrr[i][j + 1] == *"\\hline" {
-                    for _ in 0..sep {
-                        log.push(dash);
+                if vert[j] && r[j + 1] != "\\ext" {
+                    if debug_print {
+                        println!("1 - pushing {} onto row {}, j = {}", verty, i, j);
                     }
-                } else {
-                    for _ in 0..sep {
-                        log.push(' ');
+                    log.push(verty);
+                    let c = if r[j + 1] == "\\hline" { dash } else { ' ' };
+                    for _ in 0..sep[j] {
+                        log.push(c);
                     }
                 }
             }
+            if debug_print {
+                println!("2 - pushing {} onto row {}", verty, i);
+            }
+            log.push(verty);
+            process(Some((to_chars(&log), shade)))?;
+            i += 1;
         }
-        if debug_print {
-            println!("2 - pushing {} onto row {}", verty, i);
-        }
-        log.push(verty);
-        log.push('\n');
     }
+
+    // Create bottom boundary of table.
+
+    log.clear();
     log.push(botleft);
     for i in 0..ncols {
         let mut n = maxcol[i];
         if i < ncols - 1 {
-            n += sep;
+            n += sep[i];
         }
         for _ in 0..n {
             log.push(dash);
         }
         if vert[i] {
-            if rrr[rrr.len() - 1][i + 1] != "\\ext" {
+            if rows[rows.len() - 1][i + 1] != "\\ext" {
                 log.push(uptee);
             } else {
                 log.push(dash);
             }
-            for _ in 0..sep {
+            for _ in 0..sep[i] {
                 log.push(dash);
             }
         }
     }
     log.push(botright);
-    log.push('\n');
+    process(Some((to_chars(&log), false)))?;
+    process(None)?;
 
-    // Convert into a super-character vec of matrices.  There is one vector entry per line.
-    // In each matrix, an entry is a super_character: a rust character, together with the escape
-    // code characters that came before it.
-
-    let mut mat = Vec::<Vec<Vec<char>>>::new();
-    {
-        let mut all = Vec::<Vec<char>>::new();
-        let mut z = Vec::<char>::new();
-        for c in log.chars() {
-            if c != '\n' {
-                z.push(c);
-            } else {
-                if !z.is_empty() {
-                    all.push(z.clone());
-                }
-                z.clear();
+    // Finish.
+
+    if debug_print {
+        println!();
+    }
+    Ok(())
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Same as print_tabular_vbox, but put a title above the table, centered and boxed, and
+// sharing the width of the table.  This is done by adding a title row, consisting of a single
+// entry extended across all columns, followed by an \hline row.  If the title is wider than
+// the table, the last column is widened.
+
+pub fn print_tabular_vbox_titled(
+    log: &mut String,
+    title: &str,
+    rows: &[Vec<String>],
+    sep: usize,
+    justify: &[u8],
+    style: impl Into<BoxStyle>,
+) {
+    // Find the interior width of the table without the title.
+
+    let style = style.into();
+    let mut body = String::new();
+    print_tabular_vbox(&mut body, rows, sep, justify, false, style, false);
+    let width = visible_width(body.lines().next().unwrap()) - 2;
+
+    // Center the title.
+
+    let tw = visible_width(title);
+    let mut t = String::new();
+    if tw < width {
+        let left = (width - tw) / 2;
+        for _ in 0..left {
+            t.push(' ');
+        }
+        t += title;
+        for _ in left + tw..width {
+            t.push(' ');
+        }
+    } else {
+        t += title;
+    }
+
+    // Add the title and hline rows, and print.
+
+    let mut ncols = 0;
+    for row in rows.iter() {
+        ncols = max(ncols, row.len());
+    }
+    let mut rrr = Vec::<Vec<String>>::with_capacity(rows.len() + 2);
+    let mut row = vec![t];
+    for _ in 1..ncols {
+        row.push("\\ext".to_string());
+    }
+    rrr.push(row);
+    rrr.push(vec!["\\hline".to_string(); ncols]);
+    rrr.extend(rows.iter().cloned());
+    print_tabular_vbox(log, &rrr, sep, justify, false, style, false);
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Wrap the entries of a matrix so that each line of each entry has visible width at most
+// max_width, by breaking lines at spaces, and breaking words that are too long by themselves.
+// The lines are separated by newlines, so that print_tabular_vbox displays each entry on
+// several lines.  Lines that already fit are left unchanged, and \ext and \hline entries are
+// left as is.
+
+pub fn wrap_entries(rows: &[Vec<String>], max_width: usize) -> Vec<Vec<String>> {
+    assert!(max_width > 0);
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .map(|x| {
+                    if *x == "\\ext" || *x == "\\hline" {
+                        x.clone()
+                    } else {
+                        wrap_entry(x, max_width)
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn wrap_entry(x: &str, max_width: usize) -> String {
+    let mut lines = Vec::<String>::new();
+    for line in x.split('\n') {
+        if visible_width(line) <= max_width {
+            lines.push(line.to_string());
+            continue;
+        }
+        let (mut current, mut width) = (String::new(), 0);
+        for word in line.split_whitespace() {
+            let mut word = word.to_string();
+            let mut w = visible_width(&word);
+            if width > 0 && width + 1 + w > max_width {
+                lines.push(std::mem::take(&mut current));
+                width = 0;
+            }
+            while w > max_width {
+                let (head, tail) = split_at_width(&word, max_width);
+                lines.push(head);
+                word = tail;
+                w = visible_width(&word);
+            }
+            if width > 0 {
+                current.push(' ');
+                width += 1;
             }
+            current += &word;
+            width += w;
         }
-        if !z.is_empty() {
-            all.push(z);
-        }
-        for i in 0..all.len() {
-            mat.push(package_characters_with_escapes_char(&all[i]));
-        }
-    }
-
-    // "Smooth" edges of hlines.
-
-    for i in 0..mat.len() {
-        for j in 0..mat[i].len() {
-            if j > 0
-                && mat[i][j - 1] == vec![dash]
-                && mat[i][j] == vec![verty]
-                && j + 1 < mat[i].len()
-                && mat[i][j + 1] == vec![dash]
-                && i + 1 < mat.len()
-                && j < mat[i + 1].len()
-                && mat[i + 1][j] != vec![verty]
-            {
-                mat[i][j] = vec![uptee];
-            } else if j > 0
-                && mat[i][j - 1] == vec![dash]
-                && mat[i][j] == vec![verty]
-                && j + 1 < mat[i].len()
-                && mat[i][j + 1] == vec![dash]
-            {
-                mat[i][j] = vec![cross];
-            } else if mat[i][j] == vec![verty]
-                && j + 1 < mat[i].len()
-                && mat[i][j + 1] == vec![dash]
-            {
-                mat[i][j] = vec![lefty];
-            } else if j > 0
-                && mat[i][j - 1] == vec![dash]
-                && mat[i][j] == vec![verty]
-                && (j + 1 == mat[i].len() || mat[i][j + 1] != vec![dash])
-            {
-                mat[i][j] = vec![righty];
+        lines.push(current);
+    }
+    lines.join("\n")
+}
+
+// Split a string into a head of visible width at most n (but at least one character), and the
+// rest.  Escape sequences are kept with the character that follows them.
+
+fn split_at_width(s: &str, n: usize) -> (String, String) {
+    let (mut head, mut width) = (String::new(), 0);
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped || c == '\u{1b}' {
+            escaped = c != 'm';
+        } else {
+            let w = c.width().unwrap_or(0);
+            if width > 0 && width + w > n {
+                return (head, s[i..].to_string());
             }
+            width += w;
         }
+        head.push(c);
     }
+    (head, String::new())
+}
 
-    // Output matrix.
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
-    log.clear();
-    for i in 0..mat.len() {
-        for j in 0..mat[i].len() {
-            for k in 0..mat[i][j].len() {
-                log.push(mat[i][j][k]);
+// Print out a matrix as a GitHub-flavored Markdown table, whose header is the first row.  The
+// argument justify is as for print_tabular_vbox, except that the symbol | is ignored, as
+// Markdown tables have a bar between every two columns.  Escape sequences are removed, since
+// Markdown does not display them, vertical bars in entries are escaped, and entries are padded
+// so that the table is also readable as text.  The entries \ext and \hline are not supported,
+// as Markdown has no equivalent for them.
+
+pub fn print_tabular_markdown(log: &mut String, rows: &[Vec<String>], justify: &[u8]) {
+    try_print_tabular_markdown(log, rows, justify).unwrap_or_else(|e| panic!("{}", e));
+}
+
+// Same as print_tabular_markdown, but return an error if the table contains \ext or \hline, or
+// if the number of l, r or c symbols in justify is not the number of columns.  Nothing is
+// printed in that case.
+
+pub fn try_print_tabular_markdown(
+    log: &mut String,
+    rows: &[Vec<String>],
+    justify: &[u8],
+) -> Result<(), String> {
+    let just = justify
+        .iter()
+        .filter(|&&c| c != b'|')
+        .cloned()
+        .collect::<Vec<u8>>();
+    let mut ncols = 0;
+    for row in rows.iter() {
+        ncols = max(ncols, row.len());
+    }
+    if just.len() != ncols {
+        return Err(format!(
+            "\nError.  Your table has {} columns but the number of l, r or c symbols in \
+             justify is {}.\nThese numbers should be equal.",
+            ncols,
+            just.len()
+        ));
+    }
+    let mut rrr = Vec::<Vec<String>>::with_capacity(rows.len());
+    for row in rows.iter() {
+        let mut r = Vec::<String>::with_capacity(ncols);
+        for x in row.iter() {
+            if *x == "\\ext" || *x == "\\hline" {
+                return Err(format!(
+                    "\nError.  A Markdown table cannot contain {}, as Markdown has no \
+                     equivalent for it.",
+                    x
+                ));
             }
+            r.push(strip_ansi(x).replace('|', "\\|"));
+        }
+        r.resize(ncols, String::new());
+        rrr.push(r);
+    }
+
+    // Markdown requires at least three characters in each cell of the delimiter row.
+
+    let mut maxcol = vec![3; ncols];
+    for row in rrr.iter() {
+        for (j, x) in row.iter().enumerate() {
+            maxcol[j] = max(maxcol[j], visible_width(x));
+        }
+    }
+    let push_row = |log: &mut String, row: &[String]| {
+        log.push('|');
+        for (j, x) in row.iter().enumerate() {
+            let pad = maxcol[j] - visible_width(x);
+            let left = match just[j] {
+                b'r' => pad,
+                b'c' => pad / 2,
+                _ => 0,
+            };
+            log.push(' ');
+            log.push_str(&" ".repeat(left));
+            log.push_str(x);
+            log.push_str(&" ".repeat(pad - left));
+            log.push_str(" |");
         }
         log.push('\n');
+    };
+    if rrr.is_empty() {
+        return Ok(());
     }
+    push_row(log, &rrr[0]);
+    log.push('|');
+    for j in 0..ncols {
+        let dashes = "-".repeat(maxcol[j] - 1);
+        match just[j] {
+            b'r' => log.push_str(&format!(" {}: |", dashes)),
+            b'c' => log.push_str(&format!(" :{}: |", &dashes[1..])),
+            _ => log.push_str(&format!(" :{} |", dashes)),
+        }
+    }
+    log.push('\n');
+    for row in rrr[1..].iter() {
+        push_row(log, row);
+    }
+    Ok(())
+}
 
-    // Finish.
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
-    if debug_print {
-        println!();
+// Write out a matrix in CSV format, using a given delimiter, e.g. ',' or '\t', with one line
+// per row.  As in RFC 4180, an entry is quoted if it contains the delimiter, a quote or a line
+// break, and quotes in it are doubled.  Entries are written exactly as given, so this is for
+// data that are not intended for display by print_tabular_vbox.
+
+pub fn write_tabular_csv(log: &mut String, rows: &[Vec<String>], delimiter: char) {
+    for row in rows.iter() {
+        for (j, x) in row.iter().enumerate() {
+            if j > 0 {
+                log.push(delimiter);
+            }
+            if x.contains(&[delimiter, '"', '\n', '\r'][..]) {
+                log.push('"');
+                log.push_str(&x.replace('"', "\"\""));
+                log.push('"');
+            } else {
+                log.push_str(x);
+            }
+        }
+        log.push('\n');
     }
 }
 
+// Same as write_tabular_csv, but for a matrix that is intended for display by
+// print_tabular_vbox.  Escape sequences are removed.  Rows consisting entirely of \hline are
+// omitted, and other entries \hline or \ext are printed as empty.
+
+pub fn print_tabular_csv(log: &mut String, rows: &[Vec<String>], delimiter: char) {
+    let mut rrr = Vec::<Vec<String>>::with_capacity(rows.len());
+    for row in rows.iter() {
+        if !row.is_empty() && row.iter().all(|x| x == "\\hline") {
+            continue;
+        }
+        rrr.push(
+            row.iter()
+                .map(|x| {
+                    if *x == "\\ext" || *x == "\\hline" {
+                        String::new()
+                    } else {
+                        strip_ansi(x)
+                    }
+                })
+                .collect(),
+        );
+    }
+    write_tabular_csv(log, &rrr, delimiter);
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
 #[cfg(test)]
@@ -526,7 +1100,14 @@ mod tests {
     // run this test using:
     // cargo test -p tenkit2 test_print_tabular_vbox
 
-    use crate::print_tabular_vbox;
+    use crate::{
+        print_tabular, print_tabular_csv, print_tabular_markdown, print_tabular_vbox,
+        print_tabular_vbox_seps, print_tabular_vbox_titled, print_tabular_with_widths, strip_ansi,
+        try_print_tabular_markdown, try_print_tabular_vbox, visible_width, wrap_entries,
+        write_tabular_csv, write_tabular_vbox, BoxStyle,
+    };
+
+    use string_utils::{parse_csv, strme};
 
     // (should add some escape codes)
 
@@ -557,7 +1138,7 @@ mod tests {
         rows.push(row);
         let mut log = String::new();
         let justify = &[b'r', b'|', b'l', b'l'];
-        print_tabular_vbox(&mut log, &rows, 2, justify, false, false);
+        print_tabular_vbox(&mut log, &rows, 2, justify, false, false, false);
         let answer = "┌────────┬─────────────────────────┐\n\
                       │ omega  │  superduperfineexcellent│\n\
                       │  woof  │  snarl      octopus     │\n\
@@ -584,7 +1165,7 @@ mod tests {
         rows.push(row);
         let mut log = String::new();
         let justify = &[b'l', b'|', b'l'];
-        print_tabular_vbox(&mut log, &rows, 2, justify, false, false);
+        print_tabular_vbox(&mut log, &rows, 2, justify, false, false, false);
         let answer = "┌────────┬────────┐\n\
                       │pencil  │  pusher│\n\
                       ├────────┴────────┤\n\
@@ -598,5 +1179,541 @@ mod tests {
         if log != answer {
             panic!();
         }
+
+        // test 3: consecutive hlines, which must not be smoothed against each other
+
+        let hline = vec!["\\hline".to_string(), "\\hline".to_string()];
+        let rows = vec![
+            hline.clone(),
+            vec!["a".to_string(), "b".to_string()],
+            hline.clone(),
+            hline,
+            vec!["c".to_string(), "d".to_string()],
+        ];
+        let mut log = String::new();
+        let justify = &[b'l', b'|', b'l'];
+        print_tabular_vbox(&mut log, &rows, 2, justify, false, false, false);
+        let answer = "┌───┬───┐\n\
+                      ├───┼───┤\n\
+                      │a  │  b│\n\
+                      ├───┼───┤\n\
+                      ├───┼───┤\n\
+                      │c  │  d│\n\
+                      └───┴───┘\n";
+        if log != answer {
+            println!("\ntest 3 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_titled() {
+        // test 1
+
+        let rows = vec![
+            vec!["a".to_string(), "bb".to_string(), "ccc".to_string()],
+            vec!["dddd".to_string(), "e".to_string(), "f".to_string()],
+        ];
+        let mut log = String::new();
+        let justify = b"l|r|l";
+        print_tabular_vbox_titled(&mut log, "TITLE", &rows, 1, justify, false);
+        let answer = "┌───────────────┐\n\
+                      │     TITLE     │\n\
+                      ├─────┬────┬────┤\n\
+                      │a    │ bb │ ccc│\n\
+                      │dddd │  e │ f  │\n\
+                      └─────┴────┴────┘\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 2: title wider than the table
+
+        let mut log = String::new();
+        print_tabular_vbox_titled(&mut log, "A RATHER LONG TITLE", &rows, 1, justify, false);
+        let answer = "┌───────────────────┐\n\
+                      │A RATHER LONG TITLE│\n\
+                      ├─────┬────┬────────┤\n\
+                      │a    │ bb │ ccc    │\n\
+                      │dddd │  e │ f      │\n\
+                      └─────┴────┴────────┘\n";
+        if log != answer {
+            println!("\ntest 2 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_shaded() {
+        // run with --nocapture to see the shading
+
+        let rows = vec![
+            vec!["sample".to_string(), "cells".to_string()],
+            vec!["\\hline".to_string(), "\\hline".to_string()],
+            vec!["\u{1b}[01;31mpbmc\u{1b}[0m".to_string(), "1000".to_string()],
+            vec!["tumor".to_string(), "270".to_string()],
+            vec!["spleen".to_string(), "31".to_string()],
+        ];
+        let justify = b"l|r";
+        let mut plain = String::new();
+        print_tabular_vbox(&mut plain, &rows, 1, justify, false, false, false);
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 1, justify, false, false, true);
+        println!("\n{}", log);
+        assert_eq!(strip_ansi(&log), strip_ansi(&plain));
+        let lines = log.lines().collect::<Vec<_>>();
+        assert!(!lines[1].contains('\u{1b}'));
+        assert!(lines[3].contains("\u{1b}[01;31m\u{1b}[48;5;254mpbmc"));
+        assert!(!lines[4].contains('\u{1b}'));
+        assert_eq!(
+            lines[5],
+            "│\u{1b}[48;5;254mspleen \u{1b}[0m│\u{1b}[48;5;254m    31\u{1b}[0m│"
+        );
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_seps() {
+        // test 1
+
+        let rows = vec![
+            vec![
+                "a".to_string(),
+                "bb".to_string(),
+                "c".to_string(),
+                "d".to_string(),
+            ],
+            vec![
+                "eee".to_string(),
+                "f".to_string(),
+                "gg".to_string(),
+                "h".to_string(),
+            ],
+            vec![
+                "wide multicolumn".to_string(),
+                "\\ext".to_string(),
+                "\\ext".to_string(),
+                "i".to_string(),
+            ],
+        ];
+        let mut log = String::new();
+        let justify = b"l|rl|l";
+        print_tabular_vbox_seps(&mut log, &rows, &[1, 3, 0], justify, false, false, false);
+        let answer = "┌────┬───────────┬─┐\n\
+                      │a   │ bb   c    │d│\n\
+                      │eee │  f   gg   │h│\n\
+                      │wide multicolumn│i│\n\
+                      └────────────────┴─┘\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 2: a single separation is used for all gaps
+
+        let (mut log1, mut log2) = (String::new(), String::new());
+        print_tabular_vbox_seps(&mut log1, &rows, &[2], justify, false, false, false);
+        print_tabular_vbox(&mut log2, &rows, 2, justify, false, false, false);
+        assert_eq!(log1, log2);
+
+        // test 3: malformed justify strings
+
+        for justify in [&b"l|rlll"[..], b"|lrll", b"lrll|", b"lr|l"].iter() {
+            let mut log = String::new();
+            let r = try_print_tabular_vbox(&mut log, &rows, 1, justify, false, false, false);
+            assert!(r.is_err());
+            assert!(log.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_print_tabular_center() {
+        let rows = vec![
+            vec!["left".to_string(), "mid".to_string(), "right".to_string()],
+            vec!["a".to_string(), "bcdefgh".to_string(), "i".to_string()],
+            vec!["jk".to_string(), "lmno".to_string(), "pq".to_string()],
+        ];
+        let mut log = Vec::<u8>::new();
+        print_tabular(&mut log, &rows, 1, Some(b"lcr".to_vec()));
+        let answer = "left   mid   right\n\
+                      a    bcdefgh     i\n\
+                      jk    lmno      pq\n";
+        assert_eq!(strme(&log), answer);
+    }
+
+    #[test]
+    fn test_print_tabular_with_widths() {
+        let rows1 = vec![
+            vec!["a".to_string(), "bbbbbb".to_string(), "c".to_string()],
+            vec!["dddd".to_string(), "e".to_string(), "f".to_string()],
+        ];
+        let rows2 = vec![vec!["gggggg".to_string(), "h".to_string(), "i".to_string()]];
+        let just = Some(b"lrl".to_vec());
+        let mut scratch = Vec::<u8>::new();
+        let w1 = print_tabular_with_widths(&mut scratch, &rows1, 2, just.clone(), &[]);
+        let w2 = print_tabular_with_widths(&mut scratch, &rows2, 2, just.clone(), &[]);
+        assert_eq!(w1, vec![4, 6, 1]);
+        let w = w1
+            .iter()
+            .zip(w2.iter())
+            .map(|(a, b)| *a.max(b))
+            .collect::<Vec<_>>();
+        let (mut log1, mut log2) = (Vec::<u8>::new(), Vec::<u8>::new());
+        assert_eq!(
+            print_tabular_with_widths(&mut log1, &rows1, 2, just.clone(), &w),
+            w
+        );
+        assert_eq!(print_tabular_with_widths(&mut log2, &rows2, 2, just, &w), w);
+        assert_eq!(strme(&log1), "a       bbbbbb  c\ndddd         e  f\n");
+        assert_eq!(strme(&log2), "gggggg       h  i\n");
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_center() {
+        // test 1
+
+        let rows = vec![
+            vec!["name".to_string(), "columns".to_string(), "x".to_string()],
+            vec![
+                "\\hline".to_string(),
+                "\\hline".to_string(),
+                "\\hline".to_string(),
+            ],
+            vec!["a".to_string(), "abc".to_string(), "y".to_string()],
+            vec!["bb".to_string(), "abcd".to_string(), "z".to_string()],
+        ];
+        let mut log = String::new();
+        let justify = b"l|cr";
+        print_tabular_vbox(&mut log, &rows, 1, justify, false, false, false);
+        let answer = "┌─────┬──────────┐\n\
+                      │name │ columns x│\n\
+                      ├─────┼──────────┤\n\
+                      │a    │   abc   y│\n\
+                      │bb   │  abcd   z│\n\
+                      └─────┴──────────┘\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_print_tabular_markdown() {
+        // test 1
+
+        let rows = vec![
+            vec!["name".to_string(), "count".to_string(), "ok".to_string()],
+            vec![
+                "\u{1b}[01;31mred\u{1b}[0m".to_string(),
+                "7".to_string(),
+                "y".to_string(),
+            ],
+            vec!["a|b".to_string(), "1234567".to_string()],
+        ];
+        let mut log = String::new();
+        print_tabular_markdown(&mut log, &rows, b"l|rc");
+        let answer = "| name |   count | ok  |\n\
+                      | :--- | ------: | :-: |\n\
+                      | red  |       7 |  y  |\n\
+                      | a\\|b | 1234567 |     |\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 2: errors
+
+        let mut log = String::new();
+        assert!(try_print_tabular_markdown(&mut log, &rows, b"lr").is_err());
+        let mut rows = rows;
+        rows[2][1] = "\\ext".to_string();
+        assert!(try_print_tabular_markdown(&mut log, &rows, b"lrc").is_err());
+        rows[2][1] = "\\hline".to_string();
+        assert!(try_print_tabular_markdown(&mut log, &rows, b"lrc").is_err());
+        assert!(log.is_empty());
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_multiline() {
+        // test 1
+
+        let rows = vec![
+            vec!["id".to_string(), "notes".to_string(), "n".to_string()],
+            vec![
+                "\\hline".to_string(),
+                "\\hline".to_string(),
+                "\\hline".to_string(),
+            ],
+            vec![
+                "a".to_string(),
+                "first line\nsecond".to_string(),
+                "12".to_string(),
+            ],
+            vec!["bb".to_string(), "one".to_string(), "3".to_string()],
+        ];
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 1, b"l|lr", false, false, false);
+        let answer = "┌───┬──────────────┐\n\
+                      │id │ notes       n│\n\
+                      ├───┼──────────────┤\n\
+                      │a  │ first line 12│\n\
+                      │   │ second       │\n\
+                      │bb │ one         3│\n\
+                      └───┴──────────────┘\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_print_tabular_csv() {
+        let rows = vec![
+            vec!["name".to_string(), "value".to_string()],
+            vec!["\\hline".to_string(), "\\hline".to_string()],
+            vec!["\u{1b}[01mx, y\u{1b}[0m".to_string(), "1".to_string()],
+            vec!["say \"hi\"".to_string(), "a\tb".to_string()],
+        ];
+        let mut log = String::new();
+        print_tabular_csv(&mut log, &rows, ',');
+        assert_eq!(log, "name,value\n\"x, y\",1\n\"say \"\"hi\"\"\",a\tb\n");
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(
+            parse_csv(lines[1]),
+            vec!["x, y".to_string(), "1".to_string()]
+        );
+        let mut log = String::new();
+        print_tabular_csv(&mut log, &rows, '\t');
+        assert_eq!(log, "name\tvalue\nx, y\t1\n\"say \"\"hi\"\"\"\t\"a\tb\"\n");
+
+        // Entries are not interpreted by write_tabular_csv.
+
+        let mut log = String::new();
+        write_tabular_csv(&mut log, &rows[0..3], ',');
+        assert_eq!(
+            log,
+            "name,value\n\\hline,\\hline\n\"\u{1b}[01mx, y\u{1b}[0m\",1\n"
+        );
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_wide() {
+        assert_eq!(visible_width("\u{1b}[01m日本\u{1b}[0m"), 4);
+
+        // test 1
+
+        let rows = vec![
+            vec!["语言".to_string(), "x".to_string()],
+            vec!["English".to_string(), "y".to_string()],
+        ];
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 1, b"l|r", false, false, false);
+        let answer = "┌────────┬──┐\n\
+                      │语言    │ x│\n\
+                      │English │ y│\n\
+                      └────────┴──┘\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 2
+
+        let mut log = Vec::<u8>::new();
+        print_tabular(&mut log, &rows, 1, Some(b"rl".to_vec()));
+        assert_eq!(strme(&log), "   语言 x\nEnglish y\n");
+
+        // test 3: the right border is in the same place on every line, including for a title
+        // containing wide characters, and for an ASCII row of the same display width
+
+        let rows = vec![
+            vec!["日本語".to_string(), "1".to_string()],
+            vec!["abcdef".to_string(), "2".to_string()],
+            vec!["ab".to_string(), "3".to_string()],
+        ];
+        let mut log = String::new();
+        print_tabular_vbox_titled(&mut log, "表", &rows, 1, b"l|r", false);
+        let widths = log.lines().map(visible_width).collect::<Vec<_>>();
+        assert!(widths.iter().all(|&w| w == 12));
+        assert!(log.contains("│日本語 │ 1│\n│abcdef │ 2│\n"));
+    }
+
+    #[test]
+    fn test_wrap_entries() {
+        // test 1
+
+        let rows = vec![
+            vec!["id".to_string(), "description".to_string()],
+            vec![
+                "a".to_string(),
+                "a fairly long description of\nsomething, with averyveryverylongword".to_string(),
+            ],
+            vec!["b".to_string(), "short".to_string()],
+        ];
+        let rows = wrap_entries(&rows, 12);
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 1, b"l|l", false, false, false);
+        let answer = "┌───┬─────────────┐\n\
+                      │id │ description │\n\
+                      │a  │ a fairly    │\n\
+                      │   │ long        │\n\
+                      │   │ description │\n\
+                      │   │ of          │\n\
+                      │   │ something,  │\n\
+                      │   │ with        │\n\
+                      │   │ averyveryver│\n\
+                      │   │ ylongword   │\n\
+                      │b  │ short       │\n\
+                      └───┴─────────────┘\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 2: escape sequences do not count
+
+        let x = vec![vec!["\u{1b}[01mab cd\u{1b}[0m".to_string()]];
+        assert_eq!(wrap_entries(&x, 5), x);
+        assert_eq!(
+            wrap_entries(&x, 3)[0][0],
+            "\u{1b}[01mab\ncd\u{1b}[0m".to_string()
+        );
+    }
+
+    #[test]
+    fn test_print_tabular_vbox_styles() {
+        let rows = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["\\hline".to_string(), "\\hline".to_string()],
+            vec!["c".to_string(), "d".to_string()],
+        ];
+
+        // test 1
+
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 1, b"l|l", false, BoxStyle::Rounded, false);
+        let answer = "╭──┬──╮\n\
+                      │a │ b│\n\
+                      ├──┼──┤\n\
+                      │c │ d│\n\
+                      ╰──┴──╯\n";
+        if log != answer {
+            println!("\ntest 1 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 2
+
+        let mut log = String::new();
+        print_tabular_vbox(&mut log, &rows, 1, b"l|l", false, BoxStyle::Double, false);
+        let answer = "╔══╦══╗\n\
+                      ║a ║ b║\n\
+                      ╠══╬══╣\n\
+                      ║c ║ d║\n\
+                      ╚══╩══╝\n";
+        if log != answer {
+            println!("\ntest 2 failed");
+            println!("\nyour answer:\n{}", log);
+            println!("correct answer:\n{}", answer);
+        }
+        if log != answer {
+            panic!();
+        }
+
+        // test 3: bools are still accepted
+
+        let (mut log1, mut log2) = (String::new(), String::new());
+        print_tabular_vbox(&mut log1, &rows, 1, b"l|l", false, true, false);
+        print_tabular_vbox(&mut log2, &rows, 1, b"l|l", false, BoxStyle::Heavy, false);
+        assert_eq!(log1, log2);
+    }
+
+    #[test]
+    fn test_write_tabular_vbox() {
+        let rows = vec![
+            vec!["name".to_string(), "\\ext".to_string(), "count".to_string()],
+            vec![
+                "\\hline".to_string(),
+                "\\hline".to_string(),
+                "\\hline".to_string(),
+            ],
+            vec!["ab".to_string(), "x\ny".to_string(), "17".to_string()],
+            vec!["c".to_string(), "z".to_string(), "2".to_string()],
+        ];
+        let plain = "╭───────┬──────╮\n\
+                     │name   │ count│\n\
+                     ├───┬───┼──────┤\n\
+                     │ab │ x │    17│\n\
+                     │   │ y │      │\n\
+                     │c  │ z │     2│\n\
+                     ╰───┴───┴──────╯\n";
+        let shaded = "╭───────┬──────╮\n\
+                      │name   │ count│\n\
+                      ├───┬───┼──────┤\n\
+                      │\u{1b}[48;5;254mab \u{1b}[0m│\u{1b}[48;5;254m x \u{1b}[0m\
+                      │\u{1b}[48;5;254m    17\u{1b}[0m│\n\
+                      │\u{1b}[48;5;254m   \u{1b}[0m│\u{1b}[48;5;254m y \u{1b}[0m\
+                      │\u{1b}[48;5;254m      \u{1b}[0m│\n\
+                      │c  │ z │     2│\n\
+                      ╰───┴───┴──────╯\n";
+        for &(shade, answer) in [(false, plain), (true, shaded)].iter() {
+            let mut out = Vec::<u8>::new();
+            write_tabular_vbox(&mut out, &rows, 1, b"l|l|r", BoxStyle::Rounded, shade).unwrap();
+            assert_eq!(strme(&out), answer);
+            let mut log = String::new();
+            print_tabular_vbox(
+                &mut log,
+                &rows,
+                1,
+                b"l|l|r",
+                false,
+                BoxStyle::Rounded,
+                shade,
+            );
+            assert_eq!(log, answer);
+        }
+        let mut out = Vec::<u8>::new();
+        assert!(write_tabular_vbox(&mut out, &rows, 1, b"l|l", false, false).is_err());
+        assert!(out.is_empty());
     }
 }
@@ -9,7 +9,7 @@
 // ◼ create a reference for a new species will know the conventions used by the
 // ◼ code.
 
-use io_utils::read_to_string_safe;
+use std::{fs::read_to_string, path::Path};
 
 use vdj_ann::refx::{make_vdj_ref_data_core, RefData};
 
@@ -73,37 +73,86 @@ pub fn mouse_ref_7_0() -> &'static str {
     include_str!["../vdj_refs_7.0/mouse/fasta/regions.fa"]
 }
 
-// ids_to_use_opt: Optional hashSet of ids. If specified only reference
-// entries with id in the HashSet is used to construct RefData
+// Enumerate the bundled references, as (species, version) pairs.  The version "current" is
+// the reference given by human_ref() or mouse_ref(), and "old" is the one given by
+// human_ref_old() or mouse_ref_old().  Supplementary references are not included.
 
-pub fn make_vdj_ref_data(
+type RefFn = fn() -> &'static str;
+
+const REFERENCES: [(&str, &str, RefFn); 13] = [
+    ("human", "current", human_ref),
+    ("human", "old", human_ref_old),
+    ("human", "2.0", human_ref_2_0),
+    ("human", "3.1", human_ref_3_1),
+    ("human", "4.0", human_ref_4_0),
+    ("human", "5.0", human_ref_5_0),
+    ("human", "7.0", human_ref_7_0),
+    ("mouse", "current", mouse_ref),
+    ("mouse", "old", mouse_ref_old),
+    ("mouse", "3.1", mouse_ref_3_1),
+    ("mouse", "4.0", mouse_ref_4_0),
+    ("mouse", "5.0", mouse_ref_5_0),
+    ("mouse", "7.0", mouse_ref_7_0),
+];
+
+pub fn available_references() -> Vec<(&'static str, &'static str)> {
+    REFERENCES.iter().map(|r| (r.0, r.1)).collect()
+}
+
+// Return the bundled reference fasta for a species and version, as listed by
+// available_references(), or None if there is no such reference.
+
+pub fn reference_by_version(species: &str, version: &str) -> Option<&'static str> {
+    REFERENCES
+        .iter()
+        .find(|r| r.0 == species && r.1 == version)
+        .map(|r| (r.2)())
+}
+
+// Default locations of the IMGT references, which are used if imgt is specified but no path
+// is given.
+
+pub const DEFAULT_IMGT_HUMAN_REF: &str =
+    "/mnt/opt/refdata_cellranger/vdj/vdj_IMGT_20170916-2.1.0/fasta/regions.fa";
+pub const DEFAULT_IMGT_MOUSE_REF: &str =
+    "/mnt/opt/refdata_cellranger/vdj/vdj_IMGT_mouse_20180723-2.2.0/fasta/regions.fa";
+
+// Make reference data for a species, either from the built-in reference, or if imgt is
+// specified, from an IMGT reference fasta file at imgt_path, or if that is None, at the default
+// location for the species.  Return an error message if the species is invalid or the reference
+// file cannot be read or is empty.
+
+pub fn make_vdj_ref_data_result(
     refdata: &mut RefData,
     imgt: bool,
     species: &str,
     extended: bool,
     is_tcr: bool,
     is_bcr: bool,
-) {
-    // Necessary for lifetime management of results from read_to_string_safe
+    imgt_path: Option<&Path>,
+) -> Result<(), String> {
+    // Necessary for lifetime management of the file contents
     let x: String;
     let refx = match (imgt, species) {
         (false, "human") => human_ref(),
         (false, "mouse") => mouse_ref(),
-        (true, "human") => {
-            x = read_to_string_safe(
-                "/mnt/opt/refdata_cellranger/vdj/\
-            vdj_IMGT_20170916-2.1.0/fasta/regions.fa",
-            );
-            x.as_str()
-        }
-        (true, "mouse") => {
-            x = read_to_string_safe(
-                "/mnt/opt/refdata_cellranger/vdj/\
-            vdj_IMGT_mouse_20180723-2.2.0/fasta/regions.fa",
-            );
+        (true, "human") | (true, "mouse") => {
+            let default = if species == "human" {
+                DEFAULT_IMGT_HUMAN_REF
+            } else {
+                DEFAULT_IMGT_MOUSE_REF
+            };
+            let path = imgt_path.unwrap_or_else(|| Path::new(default));
+            x = read_to_string(path).map_err(|e| {
+                format!(
+                    "Could not read IMGT reference file \"{}\": {}.",
+                    path.display(),
+                    e
+                )
+            })?;
             x.as_str()
         }
-        _ => panic!("Invalid species {}.", &species),
+        _ => return Err(format!("Invalid species {}.", &species)),
     };
     let ext_refx = if extended && !imgt {
         match species {
@@ -115,9 +164,27 @@ pub fn make_vdj_ref_data(
         ""
     };
     if refx.is_empty() {
-        panic!("Reference file has zero length.");
+        return Err("Reference file has zero length.".to_string());
     }
     make_vdj_ref_data_core(refdata, refx, ext_refx, is_tcr, is_bcr, None);
+    Ok(())
+}
+
+// Same as make_vdj_ref_data_result, using the default IMGT reference location, but panic on
+// error.
+
+pub fn make_vdj_ref_data(
+    refdata: &mut RefData,
+    imgt: bool,
+    species: &str,
+    extended: bool,
+    is_tcr: bool,
+    is_bcr: bool,
+) {
+    let r = make_vdj_ref_data_result(refdata, imgt, species, extended, is_tcr, is_bcr, None);
+    if let Err(e) = r {
+        panic!("{}", e);
+    }
 }
 
 #[cfg(test)]
@@ -157,4 +224,27 @@ mod tests {
             panic!("\nFailed to find alignment of D region.\n");
         }
     }
+
+    #[test]
+    fn test_make_vdj_ref_data_result() {
+        let mut refdata = RefData::new();
+        let bogus = Path::new("/nonexistent/vdj_IMGT/fasta/regions.fa");
+        let r =
+            make_vdj_ref_data_result(&mut refdata, true, "human", false, true, false, Some(bogus));
+        assert!(r
+            .unwrap_err()
+            .contains("/nonexistent/vdj_IMGT/fasta/regions.fa"));
+        let r = make_vdj_ref_data_result(&mut refdata, false, "fish", false, true, false, None);
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_reference_by_version() {
+        for (species, version) in available_references() {
+            let r = reference_by_version(species, version).unwrap();
+            assert!(r.starts_with('>'), "bad reference {} {}", species, version);
+        }
+        assert_eq!(reference_by_version("human", "current"), Some(human_ref()));
+        assert_eq!(reference_by_version("mouse", "2.0"), None);
+    }
 }
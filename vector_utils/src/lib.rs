@@ -3,9 +3,16 @@
 // This file contains miscellaneous vector utilities.
 
 use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Range;
 
 use superslice::Ext;
 
+// Reexported for use by the sort_sync! macro.
+
+pub use permutation;
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // DISTANCE BETWEEN TWO VECTORS
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -40,6 +47,14 @@ pub fn unique_sort<T: Ord>(x: &mut Vec<T>) {
     x.dedup();
 }
 
+// Sort a vector by a key, and then remove all but the first of the entries having each key.
+// The sort is stable, so the entry kept is the one that came first in the original vector.
+
+pub fn unique_sort_by_key<T, K: Ord, F: Fn(&T) -> K>(x: &mut Vec<T>, f: F) {
+    x.sort_by_key(|a| f(a));
+    x.dedup_by(|a, b| f(a) == f(b));
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // DOES VECTOR CONTAIN ANOTHER VECTOR
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -125,6 +140,28 @@ pub fn erase_if<T>(x: &mut Vec<T>, to_delete: &[bool]) {
     x.truncate(count);
 }
 
+// Erase elements in a vector that are flagged by another vector, and return them.  Both the
+// retained elements and the returned ones are in their original order, so x is left as
+// erase_if would leave it.  Both vectors must have the same length.  Unlike erase_if, which
+// works in place, this moves the retained elements to a new vector, so it uses O(n) extra
+// space, and x loses any excess capacity.
+
+pub fn erase_if_collect<T>(x: &mut Vec<T>, to_delete: &[bool]) -> Vec<T> {
+    assert_eq!(x.len(), to_delete.len());
+    let ndel = to_delete.iter().filter(|&&d| d).count();
+    let mut kept = Vec::<T>::with_capacity(x.len() - ndel);
+    let mut erased = Vec::<T>::with_capacity(ndel);
+    for (y, &delete) in x.drain(..).zip(to_delete.iter()) {
+        if delete {
+            erased.push(y);
+        } else {
+            kept.push(y);
+        }
+    }
+    *x = kept;
+    erased
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // INTERSECTION FUNCTIONS
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -168,6 +205,26 @@ pub fn meet_size<T: Ord>(x: &[T], y: &[T]) -> usize {
     count
 }
 
+// Same as meet_size, but faster if the first vector is much smaller than the second.  Each
+// element of the first vector is located in the second by binary search, so the run time is
+// O(small.len() * log(big.len())).
+
+pub fn meet_size_small<T: Ord>(small: &[T], big: &[T]) -> usize {
+    let mut lo = 0;
+    let mut count = 0;
+    for x in small.iter() {
+        lo += big[lo..].lower_bound(x);
+        if lo == big.len() {
+            break;
+        }
+        if big[lo] == *x {
+            count += 1;
+            lo += 1;
+        }
+    }
+    count
+}
+
 // Compute the intersection of two sorted vectors.
 
 pub fn intersection<T: Ord + Clone>(x: &[T], y: &[T], z: &mut Vec<T>) {
@@ -186,6 +243,82 @@ pub fn intersection<T: Ord + Clone>(x: &[T], y: &[T], z: &mut Vec<T>) {
     }
 }
 
+// Compute the union of two sorted vectors.  The result is sorted and has no duplicates, even
+// if the inputs do.
+
+pub fn union<T: Ord + Clone>(x: &[T], y: &[T], z: &mut Vec<T>) {
+    z.clear();
+    let (mut ix, mut iy) = (0, 0);
+    while ix < x.len() || iy < y.len() {
+        let next;
+        if iy == y.len() || (ix < x.len() && x[ix] < y[iy]) {
+            next = &x[ix];
+            ix += 1;
+        } else if ix == x.len() || y[iy] < x[ix] {
+            next = &y[iy];
+            iy += 1;
+        } else {
+            next = &x[ix];
+            ix += 1;
+            iy += 1;
+        }
+        if z.last() != Some(next) {
+            z.push(next.clone());
+        }
+    }
+}
+
+// Compute the difference of two sorted vectors, i.e. the elements of x that are not in y.  As
+// for meet_size, if an element occurs n1 times in x and n2 times in y, then it occurs
+// max(n1 - n2, 0) times in the result.
+
+pub fn difference<T: Ord + Clone>(x: &[T], y: &[T], z: &mut Vec<T>) {
+    z.clear();
+    let (mut ix, mut iy) = (0, 0);
+    while ix < x.len() {
+        if iy == y.len() || x[ix] < y[iy] {
+            z.push(x[ix].clone());
+            ix += 1;
+        } else if y[iy] < x[ix] {
+            iy += 1;
+        } else {
+            ix += 1;
+            iy += 1;
+        }
+    }
+}
+
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+// MERGE FUNCTIONS
+// ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
+
+// Merge sorted vectors into a single sorted vector, preserving duplicates.  This uses a
+// binary heap holding the current head of each input, so the cost is O(N log k) for N total
+// elements in k vectors, rather than O(N log N) for concatenating and sorting.
+
+pub fn kway_merge<T: Ord + Clone>(inputs: &[Vec<T>]) -> Vec<T> {
+    let mut n = 0;
+    for x in inputs.iter() {
+        n += x.len();
+    }
+    let mut z = Vec::<T>::with_capacity(n);
+    let mut heap = BinaryHeap::<Reverse<(T, usize)>>::with_capacity(inputs.len());
+    let mut pos = vec![0; inputs.len()];
+    for (i, x) in inputs.iter().enumerate() {
+        if !x.is_empty() {
+            heap.push(Reverse((x[0].clone(), i)));
+        }
+    }
+    while let Some(Reverse((v, i))) = heap.pop() {
+        z.push(v);
+        pos[i] += 1;
+        if pos[i] < inputs[i].len() {
+            heap.push(Reverse((inputs[i][pos[i]].clone(), i)));
+        }
+    }
+    z
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // FREQUENCY FUNCTIONS
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -214,6 +347,43 @@ pub fn make_freq<T: Ord + Clone>(x: &[T], freq: &mut Vec<(u32, T)>) {
     freq.sort_by(|a, b| b.cmp(a)); // freq.reverse_sort();
 }
 
+// Same as make_freq, but without cloning: the output consists of pairs (m,v) where v is a
+// reference to the first instance of an element in x.
+
+pub fn make_freq_ref<'a, T: Ord>(x: &'a [T], freq: &mut Vec<(u32, &'a T)>) {
+    freq.clear();
+    let mut j = 0;
+    while j < x.len() {
+        let k = next_diff(x, j);
+        freq.push(((k - j) as u32, &x[j]));
+        j = k;
+    }
+    freq.sort_by(|a, b| b.cmp(a));
+}
+
+// Run-length encode a vector: replace each maximal run of equal consecutive elements by
+// (element, length of run).  Unlike make_freq, the input need not be sorted, and the order of
+// the elements is preserved.  rle_decode inverts this.
+
+pub fn rle<T: Eq + Clone>(x: &[T]) -> Vec<(T, usize)> {
+    let mut runs = Vec::<(T, usize)>::new();
+    for v in x.iter() {
+        match runs.last_mut() {
+            Some(r) if r.0 == *v => r.1 += 1,
+            _ => runs.push((v.clone(), 1)),
+        }
+    }
+    runs
+}
+
+pub fn rle_decode<T: Clone>(runs: &[(T, usize)]) -> Vec<T> {
+    let mut x = Vec::<T>::with_capacity(runs.iter().map(|r| r.1).sum());
+    for (v, n) in runs.iter() {
+        x.resize(x.len() + n, v.clone());
+    }
+    x
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // MEMBERSHIP
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -323,6 +493,25 @@ pub fn upper_bound1_3<S: Ord + ?Sized, T: Ord, U: Ord>(x: &[(impl Borrow<S>, T,
     x.upper_bound_by_key(&d, |(a, _b, _c)| a.borrow()) as i64
 }
 
+// Find the range of instances of a given element in a sorted vector, i.e. the range from the
+// lower bound to the upper bound.  The 1_2 and 1_3 versions do this for the first entry in a
+// vector of tuples.
+
+pub fn equal_range<T: Ord + ?Sized>(x: &[impl Borrow<T>], d: &T) -> Range<usize> {
+    x.equal_range_by(|y| y.borrow().cmp(d))
+}
+
+pub fn equal_range1_2<S: Ord + ?Sized, T: Ord>(x: &[(impl Borrow<S>, T)], d: &S) -> Range<usize> {
+    x.equal_range_by_key(&d, |(a, _b)| a.borrow())
+}
+
+pub fn equal_range1_3<S: Ord + ?Sized, T: Ord, U: Ord>(
+    x: &[(impl Borrow<S>, T, U)],
+    d: &S,
+) -> Range<usize> {
+    x.equal_range_by_key(&d, |(a, _b, _c)| a.borrow())
+}
+
 // Compute the number of instances of a given element in a sorted vector.
 
 pub fn count_instances<T: Ord + ?Sized>(x: &[impl Borrow<T>], d: &T) -> i32 {
@@ -337,69 +526,59 @@ pub fn count_instances<T: Ord + ?Sized>(x: &[impl Borrow<T>], d: &T) -> i32 {
 // first position.
 
 pub fn next_diff<T: Eq>(x: &[T], i: usize) -> usize {
-    let mut j = i + 1;
-    loop {
-        if j == x.len() || x[j] != x[i] {
-            return j;
+    next_diff_by_key(x, i, |t| t)
+}
+
+// Iterate over the maximal runs of equal consecutive elements in a vector, yielding each as a
+// subslice.  For a sorted vector, these are the groups of identical elements.
+
+pub fn group_runs<T: Eq>(x: &[T]) -> impl Iterator<Item = &[T]> {
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        if i == x.len() {
+            return None;
         }
+        let j = next_diff(x, i);
+        let run = &x[i..j];
+        i = j;
+        Some(run)
+    })
+}
+
+// Find first element after x[i] whose key differs from that of x[i], or x.len() if there is
+// none.  For example, next_diff_by_key(x, i, |t| &t.0) finds the first element that is
+// different in the first position, and next_diff_by_key(x, i, |t| (&t.0, &t.1)) finds the
+// first one that is different in the first or second position.  The function next_diff, and
+// the functions below, for tuples of particular sizes, all reduce to this.  New code should
+// use this instead of the latter, as it avoids their i32 indices.
+
+pub fn next_diff_by_key<'a, T, K: Eq, F: Fn(&'a T) -> K>(x: &'a [T], i: usize, key: F) -> usize {
+    let k = key(&x[i]);
+    let mut j = i + 1;
+    while j < x.len() && key(&x[j]) == k {
         j += 1;
     }
+    j
 }
 
 pub fn next_diff1_2<T: Eq, U: Eq>(x: &[(T, U)], i: i32) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 pub fn next_diff1_3<T: Eq, U: Eq, V: Eq>(x: &[(T, U, V)], i: i32) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 pub fn next_diff1_4<T: Eq, U: Eq, V: Eq, W: Eq>(x: &[(T, U, V, W)], i: i32) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 pub fn next_diff12_3<T: Eq, U: Eq, V: Eq>(x: &[(T, U, V)], i: i32) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32
-            || x[j as usize].0 != x[i as usize].0
-            || x[j as usize].1 != x[i as usize].1
-        {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| (&t.0, &t.1)) as i32
 }
 
 pub fn next_diff12_4<T: Eq, U: Eq, V: Eq, W: Eq>(x: &[(T, U, V, W)], i: i32) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32
-            || x[j as usize].0 != x[i as usize].0
-            || x[j as usize].1 != x[i as usize].1
-        {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| (&t.0, &t.1)) as i32
 }
 
 #[allow(clippy::type_complexity)]
@@ -407,52 +586,25 @@ pub fn next_diff12_8<S: Eq, T: Eq, U: Eq, V: Eq, W: Eq, X: Eq, Y: Eq, Z: Eq>(
     x: &[(S, T, U, V, W, X, Y, Z)],
     i: i32,
 ) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32
-            || x[j as usize].0 != x[i as usize].0
-            || x[j as usize].1 != x[i as usize].1
-        {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| (&t.0, &t.1)) as i32
 }
 
 pub fn next_diff1_5<T: Eq, U: Eq, V: Eq, W: Eq, X: Eq>(x: &[(T, U, V, W, X)], i: i32) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 pub fn next_diff1_6<T: Eq, U: Eq, V: Eq, W: Eq, X: Eq, Y: Eq>(
     x: &[(T, U, V, W, X, Y)],
     i: i32,
 ) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 pub fn next_diff1_7<T: Eq, U: Eq, V: Eq, W: Eq, X: Eq, Y: Eq, Z: Eq>(
     x: &[(T, U, V, W, X, Y, Z)],
     i: i32,
 ) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 #[allow(clippy::type_complexity)]
@@ -460,13 +612,7 @@ pub fn next_diff1_8<S: Eq, T: Eq, U: Eq, V: Eq, W: Eq, X: Eq, Y: Eq, Z: Eq>(
     x: &[(S, T, U, V, W, X, Y, Z)],
     i: i32,
 ) -> i32 {
-    let mut j: i32 = i + 1;
-    loop {
-        if j == x.len() as i32 || x[j as usize].0 != x[i as usize].0 {
-            return j;
-        }
-        j += 1;
-    }
+    next_diff_by_key(x, i as usize, |t| &t.0) as i32
 }
 
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
@@ -491,14 +637,39 @@ pub unsafe fn resize_without_setting<T>(x: &mut Vec<T>, n: usize) {
     x.set_len(n); /* unsafe */
 }
 
+/// resize_fast: Resize a vector to the given size, setting all entries to their default
+/// value.  Capacity is not reduced if it exceeds the given size.  This is a safe
+/// alternative to resize_without_setting, which should only be used where the cost
+/// of setting the entries matters.
+pub fn resize_fast<T: Copy + Default>(x: &mut Vec<T>, n: usize) {
+    x.clear();
+    x.resize(n, T::default());
+}
+
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 // SORT SYNC VECTORS
 // ▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓▓
 
+// sort_sync!(t, s1, ..., sn): sort the vector t, and apply the same permutation to the vectors
+// s1, ..., sn, which must have the same length as t.  Each argument is a &mut Vec, and any number
+// of them may be given.  The elements of t must be Ord, and the elements of the other vectors
+// must be Clone.  The functions sort_sync2, ..., sort_sync7 are the cases n = 1, ..., 6 of this.
+
+#[macro_export]
+macro_rules! sort_sync {
+    ($t:expr $(, $s:expr)* $(,)?) => {{
+        let t: &mut Vec<_> = $t;
+        let permutation = $crate::permutation::sort(&t[..]);
+        *t = permutation.apply_slice(&t[..]);
+        $(
+            let s: &mut Vec<_> = $s;
+            *s = permutation.apply_slice(&s[..]);
+        )*
+    }};
+}
+
 pub fn sort_sync2<T: Ord + Clone, S1: Ord + Clone>(t: &mut Vec<T>, s1: &mut Vec<S1>) {
-    let permutation = permutation::sort(&t[..]);
-    *t = permutation.apply_slice(&t[..]);
-    *s1 = permutation.apply_slice(&s1[..]);
+    sort_sync!(t, s1);
 }
 
 pub fn sort_sync3<T: Ord + Clone, S1: Ord + Clone, S2: Ord + Clone>(
@@ -506,10 +677,7 @@ pub fn sort_sync3<T: Ord + Clone, S1: Ord + Clone, S2: Ord + Clone>(
     s1: &mut Vec<S1>,
     s2: &mut Vec<S2>,
 ) {
-    let permutation = permutation::sort(&t[..]);
-    *t = permutation.apply_slice(&t[..]);
-    *s1 = permutation.apply_slice(&s1[..]);
-    *s2 = permutation.apply_slice(&s2[..]);
+    sort_sync!(t, s1, s2);
 }
 
 pub fn sort_sync4<T: Ord + Clone, S1: Ord + Clone, S2: Ord + Clone, S3: Ord + Clone>(
@@ -518,11 +686,7 @@ pub fn sort_sync4<T: Ord + Clone, S1: Ord + Clone, S2: Ord + Clone, S3: Ord + Cl
     s2: &mut Vec<S2>,
     s3: &mut Vec<S3>,
 ) {
-    let permutation = permutation::sort(&t[..]);
-    *t = permutation.apply_slice(&t[..]);
-    *s1 = permutation.apply_slice(&s1[..]);
-    *s2 = permutation.apply_slice(&s2[..]);
-    *s3 = permutation.apply_slice(&s3[..]);
+    sort_sync!(t, s1, s2, s3);
 }
 
 pub fn sort_sync5<
@@ -538,12 +702,7 @@ pub fn sort_sync5<
     s3: &mut Vec<S3>,
     s4: &mut Vec<S4>,
 ) {
-    let permutation = permutation::sort(&t[..]);
-    *t = permutation.apply_slice(&t[..]);
-    *s1 = permutation.apply_slice(&s1[..]);
-    *s2 = permutation.apply_slice(&s2[..]);
-    *s3 = permutation.apply_slice(&s3[..]);
-    *s4 = permutation.apply_slice(&s4[..]);
+    sort_sync!(t, s1, s2, s3, s4);
 }
 
 pub fn sort_sync6<
@@ -561,13 +720,7 @@ pub fn sort_sync6<
     s4: &mut Vec<S4>,
     s5: &mut Vec<S5>,
 ) {
-    let permutation = permutation::sort(&t[..]);
-    *t = permutation.apply_slice(&t[..]);
-    *s1 = permutation.apply_slice(&s1[..]);
-    *s2 = permutation.apply_slice(&s2[..]);
-    *s3 = permutation.apply_slice(&s3[..]);
-    *s4 = permutation.apply_slice(&s4[..]);
-    *s5 = permutation.apply_slice(&s5[..]);
+    sort_sync!(t, s1, s2, s3, s4, s5);
 }
 
 pub fn sort_sync7<
@@ -587,12 +740,254 @@ pub fn sort_sync7<
     s5: &mut Vec<S5>,
     s6: &mut Vec<S6>,
 ) {
-    let permutation = permutation::sort(&t[..]);
-    *t = permutation.apply_slice(&t[..]);
-    *s1 = permutation.apply_slice(&s1[..]);
-    *s2 = permutation.apply_slice(&s2[..]);
-    *s3 = permutation.apply_slice(&s3[..]);
-    *s4 = permutation.apply_slice(&s4[..]);
-    *s5 = permutation.apply_slice(&s5[..]);
-    *s6 = permutation.apply_slice(&s6[..]);
+    sort_sync!(t, s1, s2, s3, s4, s5, s6);
+}
+
+#[cfg(test)]
+mod tests {
+
+    // run this test using:
+    // cargo test -p vector_utils
+
+    use crate::{
+        difference, equal_range, equal_range1_2, equal_range1_3, erase_if, erase_if_collect,
+        group_runs, intersection, kway_merge, lower_bound, make_freq, make_freq_ref, meet_size,
+        meet_size_small, next_diff, next_diff12_3, next_diff1_3, next_diff_by_key, resize_fast,
+        rle, rle_decode, sort_sync2, union, unique_sort_by_key, upper_bound,
+    };
+
+    #[test]
+    fn test_unique_sort_by_key() {
+        let mut x = vec![
+            (3_u32, "c"),
+            (1, "a"),
+            (3, "x"),
+            (2, "b"),
+            (1, "y"),
+            (3, "z"),
+        ];
+        unique_sort_by_key(&mut x, |p| p.0);
+        assert_eq!(x, vec![(1, "a"), (2, "b"), (3, "c")]);
+        let mut x = Vec::<(u32, &str)>::new();
+        unique_sort_by_key(&mut x, |p| p.0);
+        assert!(x.is_empty());
+    }
+
+    #[test]
+    fn test_erase_if_collect() {
+        let input = vec![5, 1, 4, 1, 3, 9, 2, 6];
+        let to_delete = [false, true, true, false, false, true, false, true];
+        let mut x = input.clone();
+        let erased = erase_if_collect(&mut x, &to_delete);
+        assert_eq!(x, vec![5, 1, 3, 2]);
+        assert_eq!(erased, vec![1, 4, 9, 6]);
+        let mut y = input.clone();
+        erase_if(&mut y, &to_delete);
+        assert_eq!(x, y);
+        let mut all = x.iter().chain(erased.iter()).cloned().collect::<Vec<_>>();
+        let mut sorted_input = input;
+        all.sort_unstable();
+        sorted_input.sort_unstable();
+        assert_eq!(all, sorted_input);
+        let mut x = vec!["a", "b"];
+        assert!(erase_if_collect(&mut x, &[false, false]).is_empty());
+        assert_eq!(erase_if_collect(&mut x, &[true, true]), vec!["a", "b"]);
+        assert!(x.is_empty());
+    }
+
+    #[test]
+    fn test_next_diff_by_key() {
+        let x = vec![
+            (1, 'a', 5),
+            (1, 'b', 5),
+            (1, 'b', 6),
+            (2, 'a', 0),
+            (3, 'c', 1),
+            (3, 'c', 2),
+        ];
+        for i in 0..x.len() {
+            assert_eq!(
+                next_diff_by_key(&x, i, |t| t.0) as i32,
+                next_diff1_3(&x, i as i32)
+            );
+            assert_eq!(
+                next_diff_by_key(&x, i, |t| (&t.0, &t.1)) as i32,
+                next_diff12_3(&x, i as i32)
+            );
+        }
+        assert_eq!(next_diff_by_key(&x, 0, |t| t.2), 2);
+        assert_eq!(next_diff_by_key(&x, 5, |t| t.0), 6);
+        let y = vec![1, 1, 1, 2, 3, 3];
+        let starts = [0, 3, 4];
+        let ends = [3, 4, 6];
+        for (&i, &j) in starts.iter().zip(ends.iter()) {
+            assert_eq!(next_diff(&y, i), j);
+        }
+        assert_eq!(next_diff_by_key(&y, 0, |v| v / 2), 3);
+    }
+
+    #[test]
+    fn test_sort_sync() {
+        let mut t = vec![3, 1, 2, 0];
+        let mut s1 = vec!["d", "b", "c", "a"];
+        let mut s2 = vec![30.0, 10.0, 20.0, 0.0];
+        let mut s3 = vec![vec![3], vec![1], vec![2], vec![0]];
+        let (mut s4, mut s5, mut s6, mut s7) = (t.clone(), t.clone(), t.clone(), t.clone());
+        let mut s8 = vec!['d', 'b', 'c', 'a'];
+        sort_sync!(&mut t, &mut s1, &mut s2, &mut s3, &mut s4, &mut s5, &mut s6, &mut s7, &mut s8);
+        assert_eq!(t, vec![0, 1, 2, 3]);
+        assert_eq!(s1, vec!["a", "b", "c", "d"]);
+        assert_eq!(s2, vec![0.0, 10.0, 20.0, 30.0]);
+        assert_eq!(s3, vec![vec![0], vec![1], vec![2], vec![3]]);
+        assert!(s4 == t && s5 == t && s6 == t && s7 == t);
+        assert_eq!(s8, vec!['a', 'b', 'c', 'd']);
+        let mut t = vec![2, 1];
+        let mut s1 = vec![vec![2], vec![1]];
+        sort_sync2(&mut t, &mut s1);
+        assert_eq!(s1, vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn test_meet_size_small() {
+        let big = vec![0, 1, 1, 2, 4, 4, 4, 7, 9, 9];
+        let smalls = [
+            vec![],
+            vec![1],
+            vec![1, 1, 1],
+            vec![3, 4, 4, 5],
+            vec![-1, 0, 9, 9, 9, 10],
+            big.clone(),
+        ];
+        for small in smalls.iter() {
+            assert_eq!(meet_size_small(small, &big), meet_size(small, &big));
+            assert_eq!(meet_size_small(&big, small), meet_size(&big, small));
+        }
+        assert_eq!(meet_size_small(&[1, 1, 4, 9], &big), 4);
+    }
+
+    #[test]
+    fn test_union_difference() {
+        let x = vec![1, 2, 2, 2, 5, 7];
+        let y = vec![0, 2, 2, 5, 5, 8];
+        let mut z = vec![99];
+        union(&x, &y, &mut z);
+        assert_eq!(z, vec![0, 1, 2, 5, 7, 8]);
+        union(&x, &[], &mut z);
+        assert_eq!(z, vec![1, 2, 5, 7]);
+        difference(&x, &y, &mut z);
+        assert_eq!(z, vec![1, 2, 7]);
+        difference(&y, &x, &mut z);
+        assert_eq!(z, vec![0, 5, 8]);
+        difference(&x, &[], &mut z);
+        assert_eq!(z, x);
+        difference(&[], &y, &mut z);
+        assert!(z.is_empty());
+
+        // The intersection and difference together make up x.
+
+        let mut w = Vec::<i32>::new();
+        intersection(&x, &y, &mut w);
+        difference(&x, &y, &mut z);
+        assert_eq!(w.len() + z.len(), x.len());
+        assert_eq!(meet_size(&x, &y), w.len());
+    }
+
+    #[test]
+    fn test_resize_fast() {
+        let mut x = vec![5_u32; 3];
+        resize_fast(&mut x, 10);
+        assert_eq!(x, vec![0; 10]);
+        x[2] = 7;
+        resize_fast(&mut x, 4);
+        assert_eq!(x, vec![0; 4]);
+        assert!(x.capacity() >= 10);
+        let mut y = Vec::<(u8, i64)>::new();
+        resize_fast(&mut y, 2);
+        assert_eq!(y, vec![(0, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn test_make_freq_ref() {
+        let mut x = ["b", "a", "c", "b", "a", "b", "d"]
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        x.sort();
+        let (mut freq, mut freq_ref) = (Vec::new(), Vec::new());
+        make_freq(&x, &mut freq);
+        make_freq_ref(&x, &mut freq_ref);
+        assert_eq!(freq.len(), freq_ref.len());
+        for (a, b) in freq.iter().zip(freq_ref.iter()) {
+            assert_eq!(a.0, b.0);
+            assert_eq!(a.1, *b.1);
+        }
+        assert_eq!(freq_ref[0], (3, &"b".to_string()));
+        assert_eq!(freq_ref[3], (1, &"c".to_string()));
+        make_freq_ref(&[] as &[u8], &mut Vec::new());
+    }
+
+    #[test]
+    fn test_group_runs() {
+        assert_eq!(group_runs::<u8>(&[]).count(), 0);
+        let x = vec![4; 5];
+        assert_eq!(group_runs(&x).collect::<Vec<_>>(), vec![&x[..]]);
+        let x = vec![1, 1, 2, 3, 3, 3, 5];
+        let runs = group_runs(&x).collect::<Vec<_>>();
+        assert_eq!(runs, vec![&[1, 1][..], &[2], &[3, 3, 3], &[5]]);
+        assert_eq!(runs.concat(), x);
+        let mut y = x.clone();
+        y.dedup();
+        assert_eq!(runs.len(), y.len());
+    }
+
+    #[test]
+    fn test_equal_range() {
+        let x = vec![1, 3, 3, 3, 5, 8];
+        assert_eq!(equal_range(&x, &3), 1..4);
+        assert_eq!(equal_range(&x, &4), 4..4);
+        assert_eq!(equal_range(&x, &9), 6..6);
+        for d in 0..10 {
+            let r = equal_range(&x, &d);
+            assert_eq!(r.start as i64, lower_bound(&x, &d));
+            assert_eq!(r.end as i64, upper_bound(&x, &d));
+        }
+        let y = vec![(1, 'a'), (2, 'b'), (2, 'c'), (4, 'a')];
+        assert_eq!(equal_range1_2(&y, &2), 1..3);
+        let z = vec![("a", 1, 2), ("b", 0, 0), ("b", 1, 1)];
+        let names = equal_range1_3(&z, "b").map(|i| z[i].1).collect::<Vec<_>>();
+        assert_eq!(names, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_kway_merge() {
+        let inputs: Vec<Vec<i32>> = Vec::new();
+        assert!(kway_merge(&inputs).is_empty());
+        let inputs = vec![vec![], vec![3, 5], vec![], vec![1, 2, 3, 3, 8, 9], vec![0]];
+        assert_eq!(kway_merge(&inputs), vec![0, 1, 2, 3, 3, 3, 5, 8, 9]);
+        let inputs = vec![vec![4, 4], vec![4]];
+        assert_eq!(kway_merge(&inputs), vec![4, 4, 4]);
+    }
+
+    #[test]
+    fn test_rle() {
+        let x: Vec<u8> = Vec::new();
+        assert!(rle(&x).is_empty());
+        assert!(rle_decode::<u8>(&[]).is_empty());
+        let x = vec![1, 1, 2, 1, 1, 1, 3];
+        assert_eq!(rle(&x), vec![(1, 2), (2, 1), (1, 3), (3, 1)]);
+        for x in [
+            b"".to_vec(),
+            b"a".to_vec(),
+            b"MMMMIIDMMS".to_vec(),
+            b"abcabc".to_vec(),
+        ]
+        .iter()
+        {
+            assert_eq!(rle_decode(&rle(x)), *x);
+        }
+        assert_eq!(
+            rle_decode(&[("a", 2), ("b", 0), ("c", 1)]),
+            vec!["a", "a", "c"]
+        );
+    }
 }